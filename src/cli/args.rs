@@ -6,16 +6,27 @@ use clap::{Parser, Subcommand};
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    #[arg(long = "config", global = true, value_name = "KEY=VALUE", help = "Override a config setting for this run")]
+    pub config: Vec<String>,
 }
 
 #[derive(Parser, Debug)]
-pub struct InitArgs {}
+pub struct InitArgs {
+    #[arg(long, help = "Main branch; detected from the remote's HEAD or a local main/master when not given")]
+    pub main: Option<String>,
+
+    #[arg(short, long, help = "Accept the defaults instead of asking")]
+    pub yes: bool,
+}
 
 #[derive(Parser, Debug)]
 pub struct CheckoutArgs {
     pub name: String,
     #[arg(short, long)]
     pub create: bool,
+    #[arg(long, help = "Branch the stack is based on, instead of MAIN_BRANCH_NAME")]
+    pub base: Option<String>,
 }
 
 #[derive(Parser, Debug)]
@@ -25,7 +36,139 @@ pub struct DeleteArgs {
 
 #[derive(Parser, Debug)]
 pub struct PushArgs {
+    #[arg(help = "Branch to push (defaults to the checked-out branch)")]
+    pub branch: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct CreateArgs {
     pub branch: String,
+
+    #[arg(short, long, help = "Commit the staged changes on the new branch")]
+    pub message: Option<String>,
+
+    #[arg(long, help = "Create the branch after this index instead of at the top")]
+    pub insert_after: Option<usize>,
+
+    #[arg(short, long, help = "Skip confirmation")]
+    pub yes: bool,
+
+    #[command(flatten)]
+    pub rebase_options: RebaseOptionArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct SplitArgs {
+    #[arg(help = "Index or name of the branch to split (defaults to the checked-out branch)")]
+    pub branch: Option<String>,
+
+    #[arg(short, long, help = "Assign commits in $EDITOR instead of interactively")]
+    pub editor: bool,
+
+    #[arg(short, long, help = "Skip confirmation")]
+    pub yes: bool,
+
+    #[command(flatten)]
+    pub rebase_options: RebaseOptionArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct FoldArgs {
+    #[arg(help = "Index or name of the branch to fold into its parent")]
+    pub target: String,
+
+    #[arg(short, long, help = "Squash the folded commits into one commit")]
+    pub squash: bool,
+
+    #[arg(long, overrides_with = "delete_branch", help = "Keep the folded git branch")]
+    pub keep_branch: bool,
+
+    #[arg(long, overrides_with = "keep_branch", help = "Delete the folded git branch")]
+    pub delete_branch: bool,
+
+    #[arg(short, long, help = "Skip confirmation")]
+    pub yes: bool,
+
+    #[command(flatten)]
+    pub rebase_options: RebaseOptionArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct MoveArgs {
+    #[arg(help = "Index or name of the branch to move")]
+    pub from: String,
+
+    #[arg(help = "Index or name of the position to move it to")]
+    pub to: String,
+
+    #[command(flatten)]
+    pub rebase_options: RebaseOptionArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct ModifyArgs {
+    #[arg(short, long, help = "Create a new commit with this message instead of amending")]
+    pub message: Option<String>,
+
+    #[arg(short, long, help = "Push the modified branch and its restacked descendants")]
+    pub push: bool,
+
+    #[arg(short, long, help = "Skip confirmation")]
+    pub yes: bool,
+
+    #[command(flatten)]
+    pub rebase_options: RebaseOptionArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct AbsorbArgs {
+    #[arg(short = 'n', long, help = "Only show which branch each hunk would go to")]
+    pub dry_run: bool,
+
+    #[arg(short, long, help = "Skip confirmation")]
+    pub yes: bool,
+
+    #[command(flatten)]
+    pub rebase_options: RebaseOptionArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct EditArgs {
+    #[arg(short, long, help = "Skip confirmation")]
+    pub yes: bool,
+
+    #[command(flatten)]
+    pub rebase_options: RebaseOptionArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct FetchPrArgs {
+    #[arg(help = "Number of the top pull request of the stack")]
+    pub number: u64,
+
+    #[arg(long, help = "Name of the stack to create (defaults to pr-<number>)")]
+    pub name: Option<String>,
+
+    #[arg(long, help = "Branch the bottom pull request targets, recorded as the stack's base")]
+    pub base: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct SubmitArgs {
+    #[arg(short, long, help = "Skip confirmation")]
+    pub yes: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct LandArgs {
+    #[arg(long, help = "How to merge: merge, squash or rebase (defaults to LAND_MERGE_METHOD)")]
+    pub method: Option<String>,
+
+    #[arg(short, long, help = "Skip confirmation")]
+    pub yes: bool,
+
+    #[command(flatten)]
+    pub rebase_options: RebaseOptionArgs,
 }
 
 #[derive(Parser, Debug)]
@@ -38,7 +181,79 @@ pub struct ShiftArgs {}
 pub struct ListArgs {}
 
 #[derive(Parser, Debug)]
-pub struct StatusArgs {}
+pub struct StatusArgs {
+    #[arg(long, help = "Show files changed and lines added/removed per branch")]
+    pub stat: bool,
+
+    #[arg(long, help = "Show pull request state, review decision and checks per branch")]
+    pub remote: bool,
+
+    #[arg(long, requires = "remote", help = "Ignore cached pull request statuses")]
+    pub refresh: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct DiffArgs {
+    #[arg(help = "Index or name of the branch (defaults to the checked-out branch)")]
+    pub target: Option<String>,
+
+    #[arg(long, help = "Show a diffstat instead of the full diff")]
+    pub stat: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct LogArgs {}
+
+#[derive(Parser, Debug)]
+pub struct WhichArgs {
+    #[arg(help = "Branch to look up (defaults to the checked-out branch)")]
+    pub branch: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct NavigateArgs {
+    #[arg(long, help = "Stash uncommitted changes and reapply them after switching")]
+    pub autostash: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct UpArgs {
+    #[arg(default_value_t = 1)]
+    pub steps: usize,
+
+    #[command(flatten)]
+    pub navigate: NavigateArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct DownArgs {
+    #[arg(default_value_t = 1)]
+    pub steps: usize,
+
+    #[command(flatten)]
+    pub navigate: NavigateArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct TopArgs {
+    #[command(flatten)]
+    pub navigate: NavigateArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct BottomArgs {
+    #[command(flatten)]
+    pub navigate: NavigateArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct GotoArgs {
+    #[arg(help = "Index or name of the branch to check out")]
+    pub target: String,
+
+    #[command(flatten)]
+    pub navigate: NavigateArgs,
+}
 
 #[derive(Parser, Debug)]
 pub struct RebaseArgs {
@@ -51,8 +266,44 @@ pub struct RebaseArgs {
     #[arg(long, help = "Rebase the bottom of the stack onto main branch")]
     pub onto_main: bool,
 
+    #[arg(long, value_name = "COMMIT", requires = "from", help = "Tip of the --from branch before it was rewritten; restacks only the branches above it")]
+    pub old_tip: Option<String>,
+
     #[arg(short, long, help = "Skip confirmation")]
     pub yes: bool,
+
+    #[command(flatten)]
+    pub rebase_options: RebaseOptionArgs,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct RebaseOptionArgs {
+    #[arg(long, overrides_with = "no_gpg_sign", help = "GPG/SSH-sign rebased commits")]
+    pub gpg_sign: bool,
+
+    #[arg(long, overrides_with = "gpg_sign", help = "Do not sign rebased commits")]
+    pub no_gpg_sign: bool,
+
+    #[arg(long, overrides_with = "verify", help = "Skip git hooks while rebasing")]
+    pub no_verify: bool,
+
+    #[arg(long, overrides_with = "no_verify", help = "Run git hooks while rebasing")]
+    pub verify: bool,
+
+    #[arg(long, overrides_with = "no_committer_date_is_author_date", help = "Keep the author date as the committer date")]
+    pub committer_date_is_author_date: bool,
+
+    #[arg(long, overrides_with = "committer_date_is_author_date", help = "Use the current time as the committer date")]
+    pub no_committer_date_is_author_date: bool,
+
+    #[arg(short = 'X', long, help = "Pass a merge strategy option to git (e.g. ours, theirs)")]
+    pub strategy_option: Option<String>,
+
+    #[arg(long, overrides_with = "no_rebase_merges", help = "Preserve merge commits while rebasing")]
+    pub rebase_merges: bool,
+
+    #[arg(long, overrides_with = "rebase_merges", help = "Flatten merge commits while rebasing")]
+    pub no_rebase_merges: bool,
 }
 
 #[derive(Parser, Debug)]
@@ -69,8 +320,86 @@ pub struct RemoveArgs {
 }
 
 #[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct ConfigArgs {
-    pub setting: String,
+    #[command(subcommand)]
+    pub command: Option<ConfigCommand>,
+
+    #[arg(required_unless_present_any = ["show_origin", "help_keys"], help = "KEY=VALUE to set")]
+    pub setting: Option<String>,
+
+    #[arg(long, conflicts_with = "setting", help = "Show every effective setting and where it was set")]
+    pub show_origin: bool,
+
+    #[arg(long, conflicts_with_all = ["setting", "show_origin"], help = "List every key with its type, default and description")]
+    pub help_keys: bool,
+
+    #[arg(long, help = "Set MAIN_BRANCH_NAME or a PR_* setting for the current stack only")]
+    pub stack: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct ConfigGetArgs {
+    pub key: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct ConfigListArgs {
+    #[arg(long, help = "Show where each setting was set")]
+    pub show_origin: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct ConfigUnsetArgs {
+    pub key: String,
+
+    #[arg(long, help = "Unset MAIN_BRANCH_NAME or a PR_* setting of the current stack")]
+    pub stack: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    #[command(about = "Print the effective value of a key")]
+    Get(ConfigGetArgs),
+
+    #[command(about = "Print every effective setting")]
+    List(ConfigListArgs),
+
+    #[command(about = "Remove a key from the repository config")]
+    Unset(ConfigUnsetArgs),
+}
+
+#[derive(Parser, Debug)]
+pub struct HooksArgs {
+    #[command(subcommand)]
+    pub command: HooksCommand,
+}
+
+#[derive(Parser, Debug)]
+pub struct HooksInstallArgs {}
+
+#[derive(Parser, Debug)]
+pub struct PostCheckoutArgs {
+    pub previous_head: String,
+    pub new_head: String,
+    pub branch_checkout: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct PostRewriteArgs {
+    pub command: String,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HooksCommand {
+    #[command(about = "Install post-checkout and post-rewrite git hooks")]
+    Install(HooksInstallArgs),
+
+    #[command(hide = true)]
+    PostCheckout(PostCheckoutArgs),
+
+    #[command(hide = true)]
+    PostRewrite(PostRewriteArgs),
 }
 
 #[derive(Subcommand, Debug)]
@@ -87,6 +416,36 @@ pub enum Commands {
     #[command()]
     Push(PushArgs),
 
+    #[command()]
+    Create(CreateArgs),
+
+    #[command()]
+    Split(SplitArgs),
+
+    #[command()]
+    Fold(FoldArgs),
+
+    #[command(name = "move", alias = "mv")]
+    Move(MoveArgs),
+
+    #[command()]
+    Modify(ModifyArgs),
+
+    #[command()]
+    Absorb(AbsorbArgs),
+
+    #[command()]
+    Edit(EditArgs),
+
+    #[command()]
+    Submit(SubmitArgs),
+
+    #[command()]
+    Land(LandArgs),
+
+    #[command(name = "fetch-pr")]
+    FetchPr(FetchPrArgs),
+
     #[command()]
     Pop(PopArgs),
 
@@ -99,6 +458,30 @@ pub enum Commands {
     #[command()]
     Status(StatusArgs),
 
+    #[command()]
+    Which(WhichArgs),
+
+    #[command()]
+    Log(LogArgs),
+
+    #[command()]
+    Diff(DiffArgs),
+
+    #[command()]
+    Up(UpArgs),
+
+    #[command()]
+    Down(DownArgs),
+
+    #[command()]
+    Top(TopArgs),
+
+    #[command()]
+    Bottom(BottomArgs),
+
+    #[command()]
+    Goto(GotoArgs),
+
     #[command()]
     Rebase(RebaseArgs),
 
@@ -110,4 +493,7 @@ pub enum Commands {
 
     #[command()]
     Config(ConfigArgs),
+
+    #[command()]
+    Hooks(HooksArgs),
 }
\ No newline at end of file
@@ -2,19 +2,54 @@ use super::args::{
     CheckoutArgs,
     DeleteArgs,
     PushArgs,
+    CreateArgs,
+    SplitArgs,
+    FoldArgs,
+    MoveArgs,
+    ModifyArgs,
+    AbsorbArgs,
+    EditArgs,
+    SubmitArgs,
+    LandArgs,
+    FetchPrArgs,
     PopArgs,
     ShiftArgs,
     ListArgs,
     StatusArgs,
+    WhichArgs,
+    LogArgs,
+    DiffArgs,
+    NavigateArgs,
+    UpArgs,
+    DownArgs,
+    TopArgs,
+    BottomArgs,
+    GotoArgs,
     RebaseArgs,
+    RebaseOptionArgs,
     InsertArgs,
     RemoveArgs,
+    InitArgs,
     ConfigArgs,
+    ConfigCommand,
+    HooksArgs,
+    HooksCommand,
+    PostCheckoutArgs,
+    PostRewriteArgs,
     Commands,
 };
 use crate::error::StackError;
-use crate::store::fs::{init, FsStore};
-use crate::git::git::Git;
+use crate::store::fs::{init, user_config, FsStore};
+use crate::git::git::{Git, RebaseOptions, INTERNAL_ENV};
+use crate::git::diff::{commute, parse_patch, Hunk};
+use super::todo::{self, TodoAction, TodoLine};
+use crate::forge::forge::{host_from_api_url, render_stack_section, replace_stack_section, Forge, ForgeKind, MergeMethod, PullRequest, PullRequestSettings, PullRequestState, PullRequestStatus, RemoteRepo};
+use crate::forge::github::GitHub;
+use crate::forge::gitlab::GitLab;
+use crate::forge::credentials::resolve_token;
+use std::collections::HashMap;
+use colored::Colorize;
+use crate::hooks::hooks::{install_hook, HOOKS};
 use crate::output::{
     error,
     success,
@@ -22,18 +57,36 @@ use crate::output::{
     confirm,
     show_stacks,
     show_stack,
+    show_branch_log,
+    format_pull_request_status,
+    register_secret,
+    show_config,
+    show_config_keys,
+    warning,
+    question_string,
 };
-use crate::config::config::Config;
+use crate::config::config::{config_key, validate, Config, CONFIG_KEYS};
+
+const MERGE_WAIT_ATTEMPTS: u32 = 60;
+const MERGE_WAIT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
 
 pub struct StackManager {
     store: FsStore,
     git: Git,
+    // Effective settings for the checked-out stack
     config: Config,
+    // The same without any stack's own settings, for other stacks
+    repo_config: Config,
 }
 
 impl StackManager {
-    pub fn new(store: FsStore, git: Git, config: Config) -> Result<Self, StackError> {
-        Ok(Self { store, git, config })
+    pub fn new(store: FsStore, git: Git, repo_config: Config) -> Result<Self, StackError> {
+        // The checked-out stack's own settings, such as its base branch
+        let mut config = repo_config.clone();
+        if let Ok(current_stack) = store.get_current_stack_path() {
+            config.apply_stack_settings(&current_stack, &store.get_stack_settings(&current_stack)?)?;
+        }
+        Ok(Self { store, git, config, repo_config })
     }
 
     fn configured_confirmation(&self, msg: &str, config_condition: bool, skip_confirmation: bool) -> Result<(bool, bool), StackError> {
@@ -45,7 +98,256 @@ impl StackManager {
         }
     }
 
+    fn rebase_options(&self, args: &RebaseOptionArgs) -> RebaseOptions {
+        let resolve = |enable: bool, disable: bool, default: bool| {
+            if enable {
+                true
+            } else if disable {
+                false
+            } else {
+                default
+            }
+        };
+        let strategy_option = args.strategy_option.clone().or_else(|| {
+            if self.config.REBASE_STRATEGY_OPTION.is_empty() {
+                None
+            } else {
+                Some(self.config.REBASE_STRATEGY_OPTION.clone())
+            }
+        });
+
+        RebaseOptions {
+            gpg_sign: resolve(args.gpg_sign, args.no_gpg_sign, self.config.REBASE_GPG_SIGN),
+            no_verify: resolve(args.no_verify, args.verify, self.config.REBASE_NO_VERIFY),
+            committer_date_is_author_date: resolve(
+                args.committer_date_is_author_date,
+                args.no_committer_date_is_author_date,
+                self.config.REBASE_COMMITTER_DATE_IS_AUTHOR_DATE,
+            ),
+            strategy_option,
+            rebase_merges: resolve(args.rebase_merges, args.no_rebase_merges, self.config.REBASE_MERGES),
+        }
+    }
+
+    fn resolve_branch(&self, branch: Option<String>) -> Result<String, StackError> {
+        match branch {
+            Some(branch) => Ok(branch),
+            None => self.git.current_branch()?.ok_or_else(|| {
+                StackError::Invalid("HEAD is detached; specify a branch name.".to_string())
+            }),
+        }
+    }
+
+    fn resolve_index(&self, contents: &[String], target: &str) -> Result<usize, StackError> {
+        if let Some(index) = contents.iter().position(|branch| branch == target) {
+            return Ok(index);
+        }
+        match target.parse::<usize>() {
+            Ok(index) if index < contents.len() => Ok(index),
+            Ok(index) => Err(StackError::Invalid(format!("Index {} is out of bounds", index))),
+            Err(_) => Err(StackError::NotFound(format!("Branch {} is not in the current stack", target))),
+        }
+    }
+
+    fn current_stack_contents(&self) -> Result<Vec<String>, StackError> {
+        let current_stack = self.store.get_current_stack_path()?;
+        self.store.get_stack_contents(&current_stack)
+    }
+
+    fn current_index(&self, contents: &[String]) -> Result<usize, StackError> {
+        let branch = self.git.current_branch()?.ok_or_else(|| {
+            StackError::Invalid("HEAD is detached; use `stack goto` instead.".to_string())
+        })?;
+        contents.iter().position(|b| *b == branch).ok_or_else(|| {
+            StackError::Invalid(format!("Checked-out branch {} is not in the current stack. Use `stack goto` instead.", branch))
+        })
+    }
+
+    fn navigate(&self, branch: &str, args: &NavigateArgs) -> Result<(), StackError> {
+        if self.git.current_branch()?.as_deref() == Some(branch) {
+            info(&format!("Already on {}", branch));
+            return Ok(());
+        }
+
+        let stashed = if self.git.is_dirty()? {
+            if !args.autostash {
+                return Err(StackError::Invalid(
+                    "Working tree has uncommitted changes. Commit them or use --autostash.".to_string()
+                ));
+            }
+            info("Stashing uncommitted changes...");
+            self.git.stash()?;
+            true
+        } else {
+            false
+        };
+
+        self.git.checkout(branch)?;
+        if stashed {
+            info("Reapplying stashed changes...");
+            self.git.stash_pop()?;
+        }
+        success(&format!("Checked out {}", branch));
+        Ok(())
+    }
+
+    fn navigate_by(&self, target: impl FnOnce(&[String]) -> Result<usize, StackError>, args: &NavigateArgs) -> Result<(), StackError> {
+        let contents = self.current_stack_contents()?;
+        if contents.is_empty() {
+            return Err(StackError::Invalid("Stack is empty".to_string()));
+        }
+        let index = target(&contents)?;
+        self.navigate(&contents[index], args)
+    }
+
+    pub fn up(&self, args: UpArgs) -> Result<(), StackError> {
+        self.navigate_by(|contents| {
+            let index = self.current_index(contents)?;
+            if index + 1 == contents.len() {
+                return Err(StackError::Invalid("Already at the top of the stack".to_string()));
+            }
+            Ok((index + args.steps).min(contents.len() - 1))
+        }, &args.navigate).map_err(|e| {
+            error(&e);
+            e
+        })
+    }
+
+    pub fn down(&self, args: DownArgs) -> Result<(), StackError> {
+        self.navigate_by(|contents| {
+            let index = self.current_index(contents)?;
+            if index == 0 {
+                return Err(StackError::Invalid("Already at the bottom of the stack".to_string()));
+            }
+            Ok(index.saturating_sub(args.steps))
+        }, &args.navigate).map_err(|e| {
+            error(&e);
+            e
+        })
+    }
+
+    pub fn top(&self, args: TopArgs) -> Result<(), StackError> {
+        self.navigate_by(|contents| Ok(contents.len() - 1), &args.navigate).map_err(|e| {
+            error(&e);
+            e
+        })
+    }
+
+    pub fn bottom(&self, args: BottomArgs) -> Result<(), StackError> {
+        self.navigate_by(|_| Ok(0), &args.navigate).map_err(|e| {
+            error(&e);
+            e
+        })
+    }
+
+    pub fn goto(&self, args: GotoArgs) -> Result<(), StackError> {
+        self.navigate_by(|contents| self.resolve_index(contents, &args.target), &args.navigate).map_err(|e| {
+            error(&e);
+            e
+        })
+    }
+
+    fn warn_if_head_elsewhere(&self, current_stack: &str, current_branch: Option<&str>) -> Result<(), StackError> {
+        let Some(branch) = current_branch else {
+            return Ok(());
+        };
+        let stacks = self.store.find_stacks_with_branch(branch)?;
+        if !stacks.is_empty() && !stacks.iter().any(|(stack, _)| stack == current_stack) {
+            let names: Vec<&str> = stacks.iter().map(|(stack, _)| stack.as_str()).collect();
+            warning(&format!(
+                "Checked-out branch {} belongs to stack {}, not the current stack {}",
+                branch,
+                names.join(", "),
+                current_stack
+            ));
+        }
+        Ok(())
+    }
+
+    // Rebases `target_branch` onto `base_branch`, then offers to push it if `push`
+    // is set. With an `upstream`, only the commits after it are replayed
+    // (`git rebase --onto`). Returns false if the user stopped the operation.
+    fn rebase_step(&self, target_branch: &str, base_branch: &str, upstream: Option<&str>, rebase_options: &RebaseOptions, skip_confirmation: bool, push: bool) -> Result<bool, StackError> {
+        let (accept, continue_op) = self.configured_confirmation(
+            &format!("Rebase {} onto {}?", target_branch, base_branch),
+            self.config.CONFIRMATION_ON_GIT_REBASE,
+            skip_confirmation
+        )?;
+
+        if !continue_op { return Ok(false); }
+        if accept {
+            info(&format!("Rebasing {} onto {}", target_branch, base_branch));
+            match upstream {
+                Some(upstream) => self.git.rebase_onto_upstream(target_branch, base_branch, upstream, rebase_options),
+                None => self.git.rebase_onto(target_branch, base_branch, rebase_options),
+            }.map_err(|e| {
+                error(&e);
+                e
+            })?;
+            if push {
+                return self.push_step(target_branch, skip_confirmation);
+            }
+        }
+        Ok(true)
+    }
+
+    // Offers to force-push the checked-out `branch`. Returns false if the
+    // user stopped the operation.
+    fn push_step(&self, branch: &str, skip_confirmation: bool) -> Result<bool, StackError> {
+        let (accept, continue_op) = self.configured_confirmation(
+            &format!("Push changes to {}?", branch),
+            self.config.CONFIRMATION_ON_GIT_PUSH,
+            skip_confirmation
+        )?;
+
+        if !continue_op { return Ok(false); }
+        if accept {
+            info(&format!("Pushing changes to {}", branch));
+            self.git.push(true).map_err(|e| {
+                error(&e);
+                e
+            })?;
+        }
+        Ok(true)
+    }
+
+    // Rebases each branch onto the one before it. Returns false if the user
+    // stopped partway through.
+    fn restack(&self, branches: &[String], rebase_options: &RebaseOptions, skip_confirmation: bool, push: bool) -> Result<bool, StackError> {
+        for window in branches.windows(2) {
+            if !self.rebase_step(&window[1], &window[0], None, rebase_options, skip_confirmation, push)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // Moves `branches` from `old_base` (a commit) onto `base`, for when the
+    // branch below them was rewritten or replaced. Every branch is rebased with
+    // `--onto` from its parent's pre-restack tip, so commits that were rewritten
+    // below it are never replayed again.
+    fn restack_onto(&self, base: &str, old_base: &str, branches: &[String], rebase_options: &RebaseOptions, skip_confirmation: bool, push: bool) -> Result<bool, StackError> {
+        let mut old_tips = vec![old_base.to_string()];
+        for branch in branches {
+            old_tips.push(self.git.rev_parse(branch)?);
+        }
+        for (i, branch) in branches.iter().enumerate() {
+            let parent = if i == 0 { base } else { &branches[i - 1] };
+            if !self.rebase_step(branch, parent, Some(&old_tips[i]), rebase_options, skip_confirmation, push)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
     pub fn checkout(&self, args: CheckoutArgs) -> Result<(), StackError> {
+        if let Some(base) = &args.base
+            && !self.git.check_branch_exists(base)?
+        {
+            let err = StackError::NotFound(format!("Base branch {} does not exist", base));
+            error(&err);
+            return Err(err);
+        }
         if args.create { 
             self.store.create_stack(&args.name).map_err(|e| {
                 error(&e);
@@ -57,6 +359,10 @@ impl StackManager {
             error(&e);
             e
         })?;
+        if let Some(base) = &args.base {
+            self.store.set_stack_setting(&args.name, "MAIN_BRANCH_NAME", Some(base))?;
+            success(&format!("Stack {} is based on {}", args.name, base));
+        }
         success(&format!("Checked out stack {}", args.name));
         Ok(())
     }
@@ -70,40 +376,1409 @@ impl StackManager {
             error(&e);
             e
         })?;
-        success(&format!("Removed stack {}", args.name));
+        success(&format!("Removed stack {}", args.name));
+        Ok(())
+    }
+
+    pub fn push(&self, args: PushArgs) -> Result<(), StackError> {
+        let branch = self.resolve_branch(args.branch).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        if !self.git.check_branch_exists(&branch).map_err(|e| {
+            error(&e);
+            e
+        })? {
+            let err = StackError::Invalid(format!("Branch {} does not exist.", branch));
+            error(&err);
+            return Err(err);
+        }
+
+        let current_stack = self.store.get_current_stack_path().map_err(|e| {
+            error(&e);
+            e
+        })?;
+
+        let stack_contents = self.store.get_stack_contents(&current_stack).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        if stack_contents.contains(&branch) {
+            let err = StackError::Invalid(format!("Branch {} already in stack.", branch));
+            error(&err);
+            return Err(err);
+        }
+
+        let current_branch = self.git.current_branch().unwrap_or(None);
+        self.warn_if_head_elsewhere(&current_stack, current_branch.as_deref()).map_err(|e| {
+            error(&e);
+            e
+        })?;
+
+        self.store.push_to_stack(&branch).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        success(&format!("Pushed branch {} to stack {}", branch, current_stack));
+        Ok(())
+    }
+
+    pub fn create(&self, args: CreateArgs) -> Result<(), StackError> {
+        let current_stack = self.store.get_current_stack_path().map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let stack_contents = self.store.get_stack_contents(&current_stack).map_err(|e| {
+            error(&e);
+            e
+        })?;
+
+        let index = match args.insert_after {
+            Some(after) if after >= stack_contents.len() => {
+                let err = StackError::Invalid(format!("Index {} is out of bounds", after));
+                error(&err);
+                return Err(err);
+            }
+            Some(after) => after + 1,
+            None => stack_contents.len(),
+        };
+        let start_point = if index == 0 {
+            self.config.MAIN_BRANCH_NAME.clone()
+        } else {
+            stack_contents[index - 1].clone()
+        };
+
+        if args.message.is_some() && !self.git.has_staged_changes().map_err(|e| {
+            error(&e);
+            e
+        })? {
+            let err = StackError::Invalid("No staged changes to commit.".to_string());
+            error(&err);
+            return Err(err);
+        }
+
+        info(&format!("Creating {} from {}", args.branch, start_point));
+        self.git.create_branch(&args.branch, &start_point).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        if let Some(message) = &args.message {
+            self.git.commit(message).map_err(|e| {
+                error(&e);
+                e
+            })?;
+        }
+
+        self.store.insert_into_stack(&args.branch, index).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        success(&format!("Created branch {} at index {} of stack {}", args.branch, index, current_stack));
+
+        // Only a branch with its own commits changes what its descendants sit on
+        if index < stack_contents.len() && args.message.is_some() {
+            let rebase_options = self.rebase_options(&args.rebase_options);
+            let mut branches = vec![args.branch.clone()];
+            branches.extend_from_slice(&stack_contents[index..]);
+            if !self.restack(&branches, &rebase_options, args.yes, true)? {
+                return Ok(());
+            }
+            self.git.checkout(&args.branch).map_err(|e| {
+                error(&e);
+                e
+            })?;
+            success("Descendants restacked successfully");
+        }
+        Ok(())
+    }
+
+    fn parent_branch(&self, contents: &[String], index: usize) -> String {
+        if index == 0 {
+            self.config.MAIN_BRANCH_NAME.clone()
+        } else {
+            contents[index - 1].clone()
+        }
+    }
+
+    fn assign_commits_interactively(&self, branch: &str, commits: &[(String, String)]) -> Result<Vec<String>, StackError> {
+        let mut assignments = Vec::new();
+        let mut previous = format!("{}-1", branch);
+        for (sha, subject) in commits {
+            let name = question_string(&format!("Branch for {} {}? (default: {})", sha, subject, previous), &previous)?;
+            let name = if name.is_empty() { previous.clone() } else { name };
+            previous = name.clone();
+            assignments.push(name);
+        }
+        Ok(assignments)
+    }
+
+    fn assign_commits_in_editor(&self, branch: &str, commits: &[(String, String)]) -> Result<Vec<String>, StackError> {
+        let mut todo = String::new();
+        for (sha, subject) in commits {
+            todo.push_str(&format!("{} {} {}\n", branch, sha, subject));
+        }
+        todo.push_str(&format!("\n# Split {} by changing the branch name on each line.\n", branch));
+        todo.push_str("# Commits stay in this order; each run of lines with the same name\n");
+        todo.push_str("# becomes one branch, stacked in the order they appear.\n");
+
+        let edited = self.git.edit("STACK_SPLIT_TODO", &todo)?;
+        let mut assignments = Vec::new();
+        for line in edited.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+            let mut parts = line.split_whitespace();
+            let (Some(name), Some(sha)) = (parts.next(), parts.next()) else {
+                return Err(StackError::Invalid(format!("Cannot parse line: {}", line)));
+            };
+            let expected = commits.get(assignments.len()).map(|(sha, _)| sha.as_str());
+            if expected != Some(sha) {
+                return Err(StackError::Invalid(
+                    "Commits must not be reordered, added or removed when splitting".to_string()
+                ));
+            }
+            assignments.push(name.to_string());
+        }
+        if assignments.len() != commits.len() {
+            return Err(StackError::Invalid(
+                "Commits must not be reordered, added or removed when splitting".to_string()
+            ));
+        }
+        Ok(assignments)
+    }
+
+    pub fn split(&self, args: SplitArgs) -> Result<(), StackError> {
+        let contents = self.current_stack_contents().map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let target = self.resolve_branch(args.branch).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let index = self.resolve_index(&contents, &target).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let branch = contents[index].clone();
+        let parent = self.parent_branch(&contents, index);
+
+        let commits = self.git.commits(&parent, &branch).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        if commits.len() < 2 {
+            let err = StackError::Invalid(format!("{} has fewer than two commits since {}", branch, parent));
+            error(&err);
+            return Err(err);
+        }
+
+        info(&format!("Commits on {} since {}:", branch, parent));
+        for (sha, subject) in &commits {
+            println!("  {} {}", sha, subject);
+        }
+
+        let assignments = if args.editor {
+            self.assign_commits_in_editor(&branch, &commits)
+        } else {
+            self.assign_commits_interactively(&branch, &commits)
+        }.map_err(|e| {
+            error(&e);
+            e
+        })?;
+
+        // Each run of consecutive commits with the same name becomes one branch,
+        // whose tip is the last commit of the run.
+        let mut groups: Vec<(String, String)> = Vec::new();
+        for (name, (sha, _)) in assignments.iter().zip(&commits) {
+            match groups.last_mut() {
+                Some((last_name, tip)) if last_name == name => *tip = sha.clone(),
+                _ => groups.push((name.clone(), sha.clone())),
+            }
+        }
+
+        let mut names: Vec<String> = Vec::new();
+        for (name, _) in &groups {
+            let err = if names.contains(name) {
+                Some(format!("Branch {} must be assigned a contiguous run of commits", name))
+            } else if *name != branch && contents.contains(name) {
+                Some(format!("Branch {} is already in the stack", name))
+            } else if *name != branch && self.git.check_branch_exists(name)? {
+                Some(format!("Branch {} already exists", name))
+            } else {
+                None
+            };
+            if let Some(msg) = err {
+                let err = StackError::Invalid(msg);
+                error(&err);
+                return Err(err);
+            }
+            names.push(name.clone());
+        }
+
+        if names.len() == 1 && names[0] == branch {
+            info("Nothing to split");
+            return Ok(());
+        }
+
+        if self.git.is_dirty().map_err(|e| {
+            error(&e);
+            e
+        })? {
+            let err = StackError::Invalid("Working tree has uncommitted changes.".to_string());
+            error(&err);
+            return Err(err);
+        }
+
+        let original_tip = commits[commits.len() - 1].0.clone();
+        let branch_tip = self.git.rev_parse(&branch)?;
+        let original_branch = self.git.current_branch()?;
+        let original_head = self.git.rev_parse("HEAD")?;
+        let restore_head = || match &original_branch {
+            Some(branch) => self.git.checkout(branch),
+            None => self.git.checkout_detached(&original_head),
+        };
+
+        // Detach so that the branch being split can move even when checked out
+        self.git.checkout_detached(&original_head).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let mut created: Vec<&String> = Vec::new();
+        for (name, tip) in &groups {
+            if *name == branch && *tip == original_tip {
+                continue;
+            }
+            info(&format!("Creating {} at {}", name, tip));
+            if let Err(e) = self.git.set_branch(name, tip) {
+                // Put back what was already moved before reporting the failure
+                self.git.set_branch(&branch, &branch_tip)?;
+                for name in created {
+                    self.git.delete_branch(name)?;
+                }
+                restore_head()?;
+                error(&e);
+                return Err(e);
+            }
+            if *name != branch {
+                created.push(name);
+            }
+        }
+        restore_head().map_err(|e| {
+            error(&e);
+            e
+        })?;
+
+        self.store.replace_in_stack(index, &names).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        success(&format!("Split {} into {}", branch, names.join(", ")));
+        if !names.contains(&branch) {
+            info(&format!("Branch {} is no longer in the stack and can be deleted", branch));
+        }
+
+        // Descendants only need restacking if the top of the split moved;
+        // with every commit assigned it ends at the original tip
+        let last_tip = &groups[groups.len() - 1].1;
+        if index + 1 < contents.len() && *last_tip != original_tip {
+            let rebase_options = self.rebase_options(&args.rebase_options);
+            let mut branches = vec![names[names.len() - 1].clone()];
+            branches.extend_from_slice(&contents[index + 1..]);
+            if !self.restack(&branches, &rebase_options, args.yes, true)? {
+                return Ok(());
+            }
+            restore_head().map_err(|e| {
+                error(&e);
+                e
+            })?;
+            success("Descendants restacked successfully");
+        }
+        Ok(())
+    }
+
+    pub fn fold(&self, args: FoldArgs) -> Result<(), StackError> {
+        let contents = self.current_stack_contents().map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let index = self.resolve_index(&contents, &args.target).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        if index == 0 {
+            let err = StackError::Invalid(format!(
+                "Cannot fold the bottom of the stack into {}",
+                self.config.MAIN_BRANCH_NAME
+            ));
+            error(&err);
+            return Err(err);
+        }
+        if self.git.is_dirty().map_err(|e| {
+            error(&e);
+            e
+        })? {
+            let err = StackError::Invalid("Working tree has uncommitted changes.".to_string());
+            error(&err);
+            return Err(err);
+        }
+
+        let branch = contents[index].clone();
+        let parent = contents[index - 1].clone();
+        let rebase_options = self.rebase_options(&args.rebase_options);
+
+        // Make sure the branch sits on its parent, so folding is a fast-forward
+        if !self.git.is_ancestor(&parent, &branch)? {
+            if !self.rebase_step(&branch, &parent, None, &rebase_options, args.yes, false)? {
+                return Ok(());
+            }
+            if !self.git.is_ancestor(&parent, &branch)? {
+                info(&format!("{} was not rebased onto {}, so it was not folded", branch, parent));
+                return Ok(());
+            }
+        }
+        let old_tip = self.git.rev_parse(&branch).map_err(|e| {
+            error(&e);
+            e
+        })?;
+
+        self.git.checkout(&parent).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        if args.squash {
+            info(&format!("Squashing {} into {}", branch, parent));
+            self.git.merge_squash(&branch)
+        } else {
+            info(&format!("Folding {} into {}", branch, parent));
+            self.git.merge_fast_forward(&branch)
+        }.map_err(|e| {
+            error(&e);
+            e
+        })?;
+
+        self.store.remove_from_stack(index).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        success(&format!("Folded {} into {}", branch, parent));
+
+        let delete_branch = if args.keep_branch {
+            false
+        } else if args.delete_branch {
+            true
+        } else {
+            self.config.FOLD_DELETE_BRANCH
+        };
+        if delete_branch {
+            self.git.delete_branch(&branch).map_err(|e| {
+                error(&e);
+                e
+            })?;
+            info(&format!("Deleted branch {}", branch));
+        }
+
+        if index + 1 < contents.len() {
+            if !self.restack_onto(&parent, &old_tip, &contents[index + 1..], &rebase_options, args.yes, true)? {
+                return Ok(());
+            }
+            self.git.checkout(&parent).map_err(|e| {
+                error(&e);
+                e
+            })?;
+            success("Descendants restacked successfully");
+        }
+
+        // The parent now holds the folded commits
+        self.push_step(&parent, args.yes)?;
+        Ok(())
+    }
+
+    // Rewrites git history so the branches follow `new_order`, a reordering of
+    // (a subset of) `old_order`. Each branch's own commits are replayed onto its
+    // new parent on a detached HEAD first; branches are only moved once every
+    // rebase has succeeded, so a conflict leaves the repository untouched.
+    fn rewrite_stack(&self, old_order: &[String], new_order: &[String], rebase_options: &RebaseOptions) -> Result<(), StackError> {
+        let Some(start) = (0..new_order.len()).find(|&i| old_order.get(i) != Some(&new_order[i])) else {
+            return Ok(());
+        };
+        if old_order.is_empty() {
+            return Ok(());
+        }
+        if self.git.is_dirty()? {
+            return Err(StackError::Invalid("Working tree has uncommitted changes.".to_string()));
+        }
+
+        let original_branch = self.git.current_branch()?;
+        let original_head = self.git.rev_parse("HEAD")?;
+        let restore_head = || match &original_branch {
+            Some(branch) => self.git.checkout(branch),
+            None => self.git.checkout_detached(&original_head),
+        };
+
+        let base = self.git.merge_base(&self.config.MAIN_BRANCH_NAME, &old_order[0])?;
+        let mut old_tips = Vec::new();
+        for branch in old_order {
+            old_tips.push(self.git.rev_parse(branch)?);
+        }
+        let old_parent_tip = |branch: &String| {
+            let index = old_order.iter().position(|b| b == branch).unwrap();
+            if index == 0 { base.clone() } else { old_tips[index - 1].clone() }
+        };
+        let old_tip = |branch: &String| {
+            old_tips[old_order.iter().position(|b| b == branch).unwrap()].clone()
+        };
+
+        let mut new_tips: Vec<String> = new_order[..start].iter().map(old_tip).collect();
+        for branch in &new_order[start..] {
+            let new_parent = new_tips.last().cloned().unwrap_or_else(|| base.clone());
+            info(&format!("Replaying {} onto {}", branch, &new_parent[..new_parent.len().min(7)]));
+            match self.git.rebase_detached(&old_tip(branch), &new_parent, &old_parent_tip(branch), rebase_options) {
+                Ok(tip) => new_tips.push(tip),
+                Err(e) => {
+                    restore_head()?;
+                    let reason = match e {
+                        StackError::Git(msg) => msg,
+                        other => other.to_string(),
+                    };
+                    return Err(StackError::Git(format!(
+                        "Replaying {} failed, no branches were changed:\n{}", branch, reason
+                    )));
+                }
+            }
+        }
+
+        // HEAD is detached, so every branch (including the original) can be moved
+        for (branch, tip) in new_order.iter().zip(&new_tips).skip(start) {
+            self.git.set_branch(branch, tip)?;
+        }
+        restore_head()
+    }
+
+    pub fn move_branch(&self, args: MoveArgs) -> Result<(), StackError> {
+        let contents = self.current_stack_contents().map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let from = self.resolve_index(&contents, &args.from).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let to = self.resolve_index(&contents, &args.to).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        if from == to {
+            info("Nothing to move");
+            return Ok(());
+        }
+
+        let mut new_order = contents.clone();
+        let branch = new_order.remove(from);
+        new_order.insert(to, branch.clone());
+
+        let rebase_options = self.rebase_options(&args.rebase_options);
+        self.rewrite_stack(&contents, &new_order, &rebase_options).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        self.store.set_stack_contents(&new_order).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let current_stack = self.store.get_current_stack_path()?;
+        let labels = self.store.get_labels(&current_stack)?;
+        show_stack(&new_order, self.git.current_branch().unwrap_or(None).as_deref(), &labels, &HashMap::new());
+        success(&format!("Moved {} from index {} to {}", branch, from, to));
+        self.refresh_stack_sections(&new_order);
+        Ok(())
+    }
+
+    pub fn modify(&self, args: ModifyArgs) -> Result<(), StackError> {
+        let contents = self.current_stack_contents().map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let index = self.current_index(&contents).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let branch = contents[index].clone();
+
+        if !self.git.has_staged_changes().map_err(|e| {
+            error(&e);
+            e
+        })? {
+            let err = StackError::Invalid("No staged changes to commit.".to_string());
+            error(&err);
+            return Err(err);
+        }
+
+        let old_tip = self.git.rev_parse(&branch).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        match &args.message {
+            Some(message) => self.git.commit(message),
+            None => self.git.amend(),
+        }.map_err(|e| {
+            error(&e);
+            e
+        })?;
+        success(&format!("Modified {}", branch));
+
+        if args.push {
+            let (accept, continue_op) = self.configured_confirmation(
+                &format!("Push changes to {}?", branch),
+                self.config.CONFIRMATION_ON_GIT_PUSH,
+                args.yes
+            )?;
+            if !continue_op { return Ok(()); }
+            if accept {
+                info(&format!("Pushing changes to {}", branch));
+                self.git.push(true).map_err(|e| {
+                    error(&e);
+                    e
+                })?;
+            }
+        }
+
+        if index + 1 < contents.len() {
+            // The old tip marks where each descendant's own commits start, so the
+            // pre-amend commits are not replayed a second time.
+            let rebase_options = self.rebase_options(&args.rebase_options);
+            if !self.restack_onto(&branch, &old_tip, &contents[index + 1..], &rebase_options, args.yes, args.push)? {
+                return Ok(());
+            }
+            self.git.checkout(&branch).map_err(|e| {
+                error(&e);
+                e
+            })?;
+            success("Descendants restacked successfully");
+        }
+        Ok(())
+    }
+
+    // Finds the branch (by index) whose own commits last touched every line a
+    // hunk changes. Pure additions are attributed by their neighbouring lines.
+    fn hunk_owner(&self, path: &str, old_start: usize, old_count: usize, owners: &[(String, usize)]) -> Result<Option<usize>, StackError> {
+        let (start, end) = if old_count > 0 {
+            (old_start, old_start + old_count - 1)
+        } else {
+            (old_start.max(1), old_start + 1)
+        };
+        let shas = match self.git.blame("HEAD", path, start, end) {
+            Ok(shas) => shas,
+            // The line after an addition at the end of the file does not exist
+            Err(_) if old_count == 0 && old_start > 0 => self.git.blame("HEAD", path, old_start, old_start)?,
+            Err(_) => return Ok(None),
+        };
+
+        let mut owner = None;
+        for sha in shas {
+            let Some((_, index)) = owners.iter().find(|(commit, _)| *commit == sha) else {
+                return Ok(None);
+            };
+            if owner.is_some_and(|owner| owner != *index) {
+                return Ok(None);
+            }
+            owner = Some(*index);
+        }
+        Ok(owner)
+    }
+
+    // Whether a hunk staged against HEAD can be moved below every commit after
+    // `target`, as squashing its fixup into `target` will do, without conflicts
+    fn hunk_commutes(&self, path: &str, hunk: &Hunk, target: &str) -> Result<bool, StackError> {
+        let mut start = hunk.old_start;
+        for commit in self.git.rev_list(&format!("{}..HEAD", target))? {
+            let patch = self.git.commit_patch(&commit, path)?;
+            let Some(changes) = parse_patch(&patch).pop() else {
+                continue;
+            };
+            match commute(start, hunk.old_count, &changes) {
+                Some(moved) => start = moved,
+                None => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+
+    pub fn absorb(&self, args: AbsorbArgs) -> Result<(), StackError> {
+        let contents = self.current_stack_contents().map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let current = self.current_index(&contents).map_err(|e| {
+            error(&e);
+            e
+        })?;
+
+        // Map every commit of the branches up to HEAD to the branch that owns it
+        let mut owners: Vec<(String, usize)> = Vec::new();
+        for index in 0..=current {
+            let parent = self.parent_branch(&contents, index);
+            let range = format!("{}..{}", parent, contents[index]);
+            for sha in self.git.rev_list(&range).map_err(|e| {
+                error(&e);
+                e
+            })? {
+                owners.push((sha, index));
+            }
+        }
+
+        let patch = self.git.staged_patch().map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let files = parse_patch(&patch);
+        if files.is_empty() {
+            let err = StackError::Invalid("No staged changes to absorb.".to_string());
+            error(&err);
+            return Err(err);
+        }
+
+        // (branch index, target commit, file, hunk index)
+        let mut absorbed: Vec<(usize, String, usize, usize)> = Vec::new();
+        let mut skipped = 0;
+        for (file_index, file) in files.iter().enumerate() {
+            if file.whole_file {
+                skipped += file.hunks.len().max(1);
+                continue;
+            }
+            for (hunk_index, hunk) in file.hunks.iter().enumerate() {
+                match self.hunk_owner(&file.path, hunk.old_start, hunk.old_count, &owners)? {
+                    Some(index) => {
+                        // Fix up the newest commit of the owning branch
+                        let range = format!("{}..{}", self.parent_branch(&contents, index), contents[index]);
+                        let target = self.git.rev_list(&range)?.remove(0);
+                        if !self.hunk_commutes(&file.path, hunk, &target)? {
+                            info(&format!("{}:{} -> (conflicts with a later commit, not absorbed)", file.path, hunk.old_start));
+                            skipped += 1;
+                            continue;
+                        }
+                        info(&format!("{}:{} -> {}", file.path, hunk.old_start, contents[index]));
+                        absorbed.push((index, target, file_index, hunk_index));
+                    }
+                    None => {
+                        info(&format!("{}:{} -> (not absorbed)", file.path, hunk.old_start));
+                        skipped += 1;
+                    }
+                }
+            }
+        }
+
+        if absorbed.is_empty() {
+            let err = StackError::Invalid("No staged hunks could be matched to a branch in the stack.".to_string());
+            error(&err);
+            return Err(err);
+        }
+        if args.dry_run {
+            return Ok(());
+        }
+
+        let old_tip = self.git.rev_parse(&contents[current]).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let mut targets: Vec<&String> = Vec::new();
+        for (_, target, _, _) in &absorbed {
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        for (i, target) in targets.iter().enumerate() {
+            let mut patch = String::new();
+            for (file_index, file) in files.iter().enumerate() {
+                let hunks_for = |targets: &[&String]| -> Vec<usize> {
+                    absorbed.iter()
+                        .filter(|(_, t, f, _)| targets.contains(&t) && *f == file_index)
+                        .map(|(_, _, _, hunk)| *hunk)
+                        .collect()
+                };
+                let hunks = hunks_for(&targets[i..=i]);
+                if !hunks.is_empty() {
+                    // Earlier fixups already changed this file on top of HEAD
+                    patch.push_str(&file.select(&hunks, &hunks_for(&targets[..i])));
+                }
+            }
+            self.git.commit_fixup(&patch, target).map_err(|e| {
+                error(&e);
+                e
+            })?;
+        }
+        success(&format!("Created fixup commits for {} hunk(s)", absorbed.len()));
+        if skipped > 0 {
+            warning(&format!("{} hunk(s) could not be absorbed and stay staged", skipped));
+        }
+
+        let lowest = absorbed.iter().map(|(index, _, _, _)| *index).min().unwrap();
+        let base = self.parent_branch(&contents, lowest);
+        let (accept, continue_op) = self.configured_confirmation(
+            &format!("Squash fixups into {}..{}?", contents[lowest], contents[current]),
+            self.config.CONFIRMATION_ON_GIT_REBASE,
+            args.yes
+        )?;
+        if !continue_op || !accept { return Ok(()); }
+
+        let rebase_options = self.rebase_options(&args.rebase_options);
+        info("Squashing fixup commits...");
+        if let Err(e) = self.git.rebase_autosquash(&base, &rebase_options) {
+            error(&e);
+            // Drop the fixup commits so the absorbed hunks are staged again
+            self.git.reset_soft(&old_tip)?;
+            warning("The fixup commits were removed and the absorbed hunks are staged again");
+            return Err(e);
+        }
+
+        if current + 1 < contents.len() {
+            if !self.restack_onto(&contents[current], &old_tip, &contents[current + 1..], &rebase_options, args.yes, true)? {
+                return Ok(());
+            }
+            self.git.checkout(&contents[current]).map_err(|e| {
+                error(&e);
+                e
+            })?;
+        }
+        success("Stack absorbed successfully");
+        Ok(())
+    }
+
+    // Applies the folds, renames and labels of an edit whose restack is done.
+    // Every ref is moved before the store is written. Returns the new stack
+    // and the branches left to split.
+    fn apply_edit(&self, current_stack: &str, kept: &[&TodoLine], dropped: &[String], labels: &mut HashMap<String, String>) -> Result<(Vec<String>, Vec<String>), StackError> {
+        let original_branch = self.git.current_branch()?;
+        let mut final_order: Vec<String> = Vec::new();
+        let mut folded: Vec<(String, String)> = Vec::new();
+        for line in kept {
+            match &line.action {
+                TodoAction::Fold => {
+                    let parent = final_order[final_order.len() - 1].clone();
+                    folded.push((line.branch.clone(), parent));
+                }
+                _ => final_order.push(line.branch.clone()),
+            }
+        }
+
+        if !folded.is_empty() {
+            // Detach so that any branch, including the checked-out one, can move
+            let head = self.git.rev_parse("HEAD")?;
+            self.git.checkout_detached(&head)?;
+            for (branch, parent) in &folded {
+                let tip = self.git.rev_parse(branch)?;
+                self.git.set_branch(parent, &tip)?;
+                info(&format!("Folded {} into {}", branch, parent));
+                if self.config.FOLD_DELETE_BRANCH {
+                    self.git.delete_branch(branch)?;
+                }
+            }
+            let restore = match &original_branch {
+                Some(branch) if self.git.check_branch_exists(branch)? => branch.clone(),
+                Some(branch) => folded.iter().find(|(b, _)| b == branch).map(|(_, p)| p.clone()).unwrap(),
+                None => head,
+            };
+            if original_branch.is_some() {
+                self.git.checkout(&restore)?;
+            } else {
+                self.git.checkout_detached(&restore)?;
+            }
+        }
+
+        let mut renamed: Vec<(&String, &String)> = Vec::new();
+        let mut splits = Vec::new();
+        for line in kept {
+            match &line.action {
+                TodoAction::Rename(name) => {
+                    self.git.rename_branch(&line.branch, name)?;
+                    if let Some(index) = final_order.iter().position(|b| *b == line.branch) {
+                        final_order[index] = name.clone();
+                    }
+                    if let Some(label) = labels.remove(&line.branch) {
+                        labels.insert(name.clone(), label);
+                    }
+                    renamed.push((&line.branch, name));
+                    info(&format!("Renamed {} to {}", line.branch, name));
+                }
+                TodoAction::Label(label) if label.is_empty() => {
+                    labels.remove(&line.branch);
+                }
+                TodoAction::Label(label) => {
+                    labels.insert(line.branch.clone(), label.clone());
+                }
+                TodoAction::Split => splits.push(line.branch.clone()),
+                _ => {}
+            }
+        }
+        labels.retain(|branch, _| final_order.contains(branch));
+
+        let pull_requests = self.store.get_pull_requests()?;
+        // Keep the pull request, so the next submit updates it instead of
+        // opening another one
+        for (branch, name) in &renamed {
+            if let Some(&number) = pull_requests.get(*branch) {
+                self.store.set_pull_request(branch, None)?;
+                self.store.set_pull_request(name, Some(number))?;
+            }
+        }
+        // Forget the pull requests of branches that left the stack, unless
+        // another stack still has them
+        for branch in dropped.iter().chain(folded.iter().map(|(branch, _)| branch)) {
+            if pull_requests.contains_key(branch)
+                && self.store.find_stacks_with_branch(branch)?.iter().all(|(stack, _)| stack == current_stack)
+            {
+                self.store.set_pull_request(branch, None)?;
+            }
+        }
+        self.store.set_stack_contents(&final_order)?;
+        self.store.set_labels(current_stack, labels)?;
+        Ok((final_order, splits))
+    }
+
+    // Puts the branches and store entries of a failed edit back as they were.
+    // `tips` holds every branch of the stack before the edit.
+    fn restore_edit(
+        &self,
+        current_stack: &str,
+        tips: &[(String, String)],
+        labels: &HashMap<String, String>,
+        pull_requests: &HashMap<String, u64>,
+        new_names: &[&String],
+        original_branch: &Option<String>,
+    ) -> Result<(), StackError> {
+        let head = self.git.rev_parse("HEAD")?;
+        self.git.checkout_detached(&head)?;
+        // Renamed branches did not exist before the edit
+        for name in new_names {
+            if self.git.check_branch_exists(name)? {
+                self.git.delete_branch(name)?;
+            }
+            self.store.set_pull_request(name, None)?;
+        }
+        for (branch, tip) in tips {
+            self.git.set_branch(branch, tip)?;
+            self.store.set_pull_request(branch, pull_requests.get(branch).copied())?;
+        }
+        let branches: Vec<String> = tips.iter().map(|(branch, _)| branch.clone()).collect();
+        self.store.set_stack_contents(&branches)?;
+        self.store.set_labels(current_stack, labels)?;
+        match original_branch {
+            Some(branch) => self.git.checkout(branch),
+            None => Ok(()),
+        }
+    }
+
+    pub fn edit(&self, args: EditArgs) -> Result<(), StackError> {
+        let current_stack = self.store.get_current_stack_path().map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let contents = self.store.get_stack_contents(&current_stack).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        if contents.is_empty() {
+            let err = StackError::Invalid("Stack is empty".to_string());
+            error(&err);
+            return Err(err);
+        }
+        let mut labels = self.store.get_labels(&current_stack).map_err(|e| {
+            error(&e);
+            e
+        })?;
+
+        let edited = self.git.edit("STACK_EDIT_TODO", &todo::render(&contents, &labels)).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let lines = todo::parse(&edited, &contents).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        if lines.is_empty() {
+            info("Empty todo list, nothing changed");
+            return Ok(());
+        }
+        for line in &lines {
+            if let TodoAction::Rename(name) = &line.action && self.git.check_branch_exists(name)? {
+                let err = StackError::Invalid(format!("Cannot rename {} to {}: branch exists", line.branch, name));
+                error(&err);
+                return Err(err);
+            }
+        }
+
+        // Folded branches stay in the order for the restack, right after their
+        // parent, and are merged into it afterwards.
+        let kept: Vec<_> = lines.iter().filter(|l| l.action != TodoAction::Drop).collect();
+        // Removed lines drop their branch too
+        let dropped: Vec<String> = contents.iter()
+            .filter(|branch| !kept.iter().any(|l| l.branch == **branch))
+            .cloned()
+            .collect();
+        let new_order: Vec<String> = kept.iter().map(|l| l.branch.clone()).collect();
+        let rebase_options = self.rebase_options(&args.rebase_options);
+
+        // Everything needed to undo the edit if a step after the restack fails
+        let mut tips = Vec::new();
+        for branch in &contents {
+            tips.push((branch.clone(), self.git.rev_parse(branch)?));
+        }
+        let old_labels = labels.clone();
+        let old_pull_requests = self.store.get_pull_requests()?;
+        let original_branch = self.git.current_branch()?;
+
+        let result = self.rewrite_stack(&contents, &new_order, &rebase_options)
+            .and_then(|_| self.apply_edit(&current_stack, &kept, &dropped, &mut labels));
+        let (final_order, splits) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                error(&e);
+                let new_names: Vec<&String> = kept.iter()
+                    .filter_map(|l| match &l.action {
+                        TodoAction::Rename(name) => Some(name),
+                        _ => None,
+                    })
+                    .collect();
+                match self.restore_edit(&current_stack, &tips, &old_labels, &old_pull_requests, &new_names, &original_branch) {
+                    Ok(()) => warning("The edit was not applied; the stack is back as it was"),
+                    Err(restore_error) => error(&restore_error),
+                }
+                return Err(e);
+            }
+        };
+        show_stack(&final_order, self.git.current_branch().unwrap_or(None).as_deref(), &labels, &HashMap::new());
+        success("Stack edited successfully");
+        self.refresh_stack_sections(&final_order);
+
+        for branch in splits {
+            self.split(SplitArgs {
+                branch: Some(branch),
+                editor: true,
+                yes: args.yes,
+                rebase_options: args.rebase_options.clone(),
+            })?;
+        }
+        Ok(())
+    }
+
+    fn forge_repository(&self) -> Result<RemoteRepo, StackError> {
+        let url = self.git.remote_url(&self.config.REMOTE_NAME)?;
+        RemoteRepo::from_url(&url)
+    }
+
+    fn forge(&self) -> Result<Box<dyn Forge>, StackError> {
+        // Fully configured setups may have a remote URL that names no host
+        let remote = if self.config.FORGE_REPOSITORY.is_empty() || self.config.FORGE_API_URL.is_empty() {
+            Some(self.forge_repository()?)
+        } else {
+            self.forge_repository().ok()
+        };
+        let kind = if self.config.FORGE.is_empty() {
+            remote.as_ref().map(RemoteRepo::forge_kind).unwrap_or(ForgeKind::GitHub)
+        } else {
+            ForgeKind::parse(&self.config.FORGE)?
+        };
+        let repository = match &remote {
+            Some(remote) if self.config.FORGE_REPOSITORY.is_empty() => remote.path.clone(),
+            _ => self.config.FORGE_REPOSITORY.clone(),
+        };
+        let api_url = match (&remote, kind) {
+            (Some(remote), ForgeKind::GitHub) if self.config.FORGE_API_URL.is_empty() => remote.github_api_url(),
+            (Some(remote), ForgeKind::GitLab) if self.config.FORGE_API_URL.is_empty() => remote.gitlab_api_url(),
+            _ => self.config.FORGE_API_URL.clone(),
+        };
+        let host = match &remote {
+            Some(remote) => remote.host.clone(),
+            None => host_from_api_url(&api_url),
+        };
+        let token = resolve_token(&self.git, kind, &host)?;
+        register_secret(&token);
+        Ok(match kind {
+            ForgeKind::GitHub => Box::new(GitHub::new(&api_url, token, &repository)),
+            ForgeKind::GitLab => Box::new(GitLab::new(&api_url, token, &repository)),
+        })
+    }
+
+    // PR_* settings of the repository, overridden by those of the stack
+    fn pull_request_settings(&self, stack_name: &str) -> Result<PullRequestSettings, StackError> {
+        // Stack settings override the config files, not the environment or flags
+        let mut config = self.repo_config.clone();
+        config.apply_stack_settings(stack_name, &self.store.get_stack_settings(stack_name)?)?;
+        let list = |value: &str| -> Vec<String> {
+            value.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        Ok(PullRequestSettings {
+            draft: config.PR_DRAFT,
+            reviewers: list(&config.PR_REVIEWERS),
+            labels: list(&config.PR_LABELS),
+            title_template: config.PR_TITLE_TEMPLATE,
+        })
+    }
+
+    fn pull_request_subject(&self, parent: &str, branch: &str) -> Result<String, StackError> {
+        let commits = self.git.commits(parent, branch)?;
+        Ok(commits.first()
+            .map(|(_, subject)| subject.clone())
+            .unwrap_or_else(|| branch.to_string()))
+    }
+
+    // Rewrites the navigation section of every pull request in the stack
+    fn update_stack_sections(&self, forge: &dyn Forge, stack_name: &str, pull_requests: &[PullRequest]) -> Result<(), StackError> {
+        let references: Vec<String> = pull_requests.iter().map(|pr| forge.reference(pr.number)).collect();
+        for (index, pr) in pull_requests.iter().enumerate() {
+            let section = render_stack_section(stack_name, &references, index, &self.config.MAIN_BRANCH_NAME);
+            let body = replace_stack_section(&pr.body, &section);
+            if body != pr.body {
+                forge.update_pull_request(pr.number, &body)?;
+                info(&format!("Updated stack section of PR #{}", pr.number));
+            }
+        }
+        Ok(())
+    }
+
+    fn refetch_stack_sections(&self, contents: &[String], recorded: &HashMap<String, u64>) -> Result<(), StackError> {
+        let stack_name = self.store.get_current_stack_path()?;
+        let forge = self.forge()?;
+        let mut pull_requests = Vec::new();
+        for branch in contents {
+            if let Some(number) = recorded.get(branch) {
+                pull_requests.push(forge.get_pull_request(*number)?);
+            }
+        }
+        self.update_stack_sections(forge.as_ref(), &stack_name, &pull_requests)
+    }
+
+    // After a reorder, keeps the stack sections of already submitted pull
+    // requests in the new order. Failing to reach the forge only warns.
+    fn refresh_stack_sections(&self, contents: &[String]) {
+        let Ok(recorded) = self.store.get_pull_requests() else {
+            return;
+        };
+        if !contents.iter().any(|branch| recorded.contains_key(branch)) {
+            return;
+        }
+        if let Err(e) = self.refetch_stack_sections(contents, &recorded) {
+            warning(&format!("Could not update pull request descriptions: {}", e));
+        }
+        info("Run `stack submit` to push the restacked branches and retarget their pull requests");
+    }
+
+    pub fn submit(&self, args: SubmitArgs) -> Result<(), StackError> {
+        let contents = self.current_stack_contents().map_err(|e| {
+            error(&e);
+            e
+        })?;
+        if contents.is_empty() {
+            info("Stack is empty");
+            return Ok(());
+        }
+        let forge = self.forge().map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let pull_requests = self.store.get_pull_requests().map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let stack_name = self.store.get_current_stack_path()?;
+        let settings = self.pull_request_settings(&stack_name).map_err(|e| {
+            error(&e);
+            e
+        })?;
+
+        let mut submitted = Vec::new();
+        for (index, branch) in contents.iter().enumerate() {
+            let base = self.parent_branch(&contents, index);
+
+            let (accept, continue_op) = self.configured_confirmation(
+                &format!("Push {} to {}?", branch, self.config.REMOTE_NAME),
+                self.config.CONFIRMATION_ON_GIT_PUSH,
+                args.yes
+            )?;
+            if !continue_op { return Ok(()); }
+            if accept {
+                info(&format!("Pushing {}", branch));
+                self.git.push_branch(&self.config.REMOTE_NAME, branch).map_err(|e| {
+                    error(&e);
+                    e
+                })?;
+            }
+
+            let existing = match pull_requests.get(branch) {
+                Some(number) => Some(forge.get_pull_request(*number)),
+                None => forge.find_pull_request(branch).transpose(),
+            }.transpose().map_err(|e| {
+                error(&e);
+                e
+            })?;
+
+            if let Some(pr) = &existing
+                && pr.head != *branch
+            {
+                // Forges cannot change the head of a pull request
+                warning(&format!("PR #{} still tracks {}, so it will not show commits pushed to {}", pr.number, pr.head, branch));
+            }
+
+            let subject = self.pull_request_subject(&base, branch)?;
+            let title = settings.title(&subject, branch, &stack_name, index, contents.len());
+            let pull_request = match existing {
+                Some(pr) if pr.base == base => {
+                    info(&format!("PR #{} for {} is up to date", pr.number, branch));
+                    pr
+                }
+                Some(pr) => {
+                    info(&format!("Retargeting PR #{} from {} to {}", pr.number, pr.base, base));
+                    forge.retarget_pull_request(pr.number, &base).map_err(|e| {
+                        error(&e);
+                        e
+                    })?
+                }
+                None => {
+                    let pr = forge.create_pull_request(branch, &base, &title, "", settings.draft).map_err(|e| {
+                        error(&e);
+                        e
+                    })?;
+                    success(&format!("Created PR #{} for {}", pr.number, branch));
+                    pr
+                }
+            };
+            if pull_request.title != title {
+                info(&format!("Retitling PR #{} to {}", pull_request.number, title));
+                forge.update_title(&pull_request, &title).map_err(|e| {
+                    error(&e);
+                    e
+                })?;
+            }
+            forge.request_reviewers(&pull_request, &settings.reviewers)
+                .and_then(|_| forge.add_labels(&pull_request, &settings.labels))
+                .map_err(|e| {
+                    error(&e);
+                    e
+                })?;
+            self.store.set_pull_request(branch, Some(pull_request.number)).map_err(|e| {
+                error(&e);
+                e
+            })?;
+            info(&format!("[{}] {} -> {}: {}", index, branch, base, pull_request.url));
+            submitted.push(pull_request);
+        }
+
+        self.update_stack_sections(forge.as_ref(), &stack_name, &submitted).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        success("Stack submitted successfully");
+        Ok(())
+    }
+
+    // Waits for a merge the forge may be completing in the background
+    fn wait_for_merge(&self, forge: &dyn Forge, number: u64) -> Result<(), StackError> {
+        for attempt in 0..MERGE_WAIT_ATTEMPTS {
+            match forge.get_state(number)? {
+                PullRequestState::Merged => return Ok(()),
+                PullRequestState::Closed => {
+                    return Err(StackError::Forge(format!("PR #{} was closed without being merged", number)));
+                }
+                _ => {}
+            }
+            if attempt == 0 {
+                info(&format!("Waiting for PR #{} to merge...", number));
+            }
+            std::thread::sleep(MERGE_WAIT_INTERVAL);
+        }
+        Err(StackError::Forge(format!(
+            "PR #{} did not merge within {} seconds",
+            number,
+            MERGE_WAIT_ATTEMPTS as u64 * MERGE_WAIT_INTERVAL.as_secs()
+        )))
+    }
+
+    pub fn land(&self, args: LandArgs) -> Result<(), StackError> {
+        let contents = self.current_stack_contents().map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let Some(bottom) = contents.first().cloned() else {
+            info("Stack is empty");
+            return Ok(());
+        };
+        let main = self.config.MAIN_BRANCH_NAME.clone();
+        let method = MergeMethod::parse(args.method.as_deref().unwrap_or(&self.config.LAND_MERGE_METHOD)).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let pull_requests = self.store.get_pull_requests().map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let number = *pull_requests.get(&bottom).ok_or_else(|| {
+            let err = StackError::Invalid(format!("Branch {} has no pull request. Run `stack submit` first.", bottom));
+            error(&err);
+            err
+        })?;
+        let forge = self.forge().map_err(|e| {
+            error(&e);
+            e
+        })?;
+        if !forge.merge_methods().contains(&method) {
+            let supported: Vec<&str> = forge.merge_methods().iter().map(MergeMethod::as_str).collect();
+            let err = StackError::Invalid(format!(
+                "This forge cannot merge using {}. Use {}.",
+                method.as_str(),
+                supported.join(" or ")
+            ));
+            error(&err);
+            return Err(err);
+        }
+
+        let (accept, continue_op) = self.configured_confirmation(
+            &format!("Merge PR #{} ({}) into {} using {}?", number, bottom, main, method.as_str()),
+            true,
+            args.yes
+        )?;
+        if !accept || !continue_op { return Ok(()); }
+
+        // Taken before anything moves, so the rest of the stack can be
+        // replayed from it even if the forge squashed or rebased the branch
+        let old_tip = self.git.rev_parse(&bottom).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        info(&format!("Merging PR #{} into {}", number, main));
+        forge.merge_pull_request(number, method)
+            .and_then(|_| self.wait_for_merge(forge.as_ref(), number))
+            .map_err(|e| {
+                error(&e);
+                e
+            })?;
+        success(&format!("Merged PR #{}", number));
+
+        let remaining = contents[1..].to_vec();
+        if let Some(next) = remaining.first()
+            && let Some(next_number) = pull_requests.get(next)
+        {
+            info(&format!("Retargeting PR #{} to {}", next_number, main));
+            forge.retarget_pull_request(*next_number, &main).map_err(|e| {
+                error(&e);
+                e
+            })?;
+        }
+
+        let stack_name = self.store.get_current_stack_path()?;
+        self.store.remove_from_stack(0)?;
+        self.store.set_pull_request(&bottom, None)?;
+        let mut labels = self.store.get_labels(&stack_name)?;
+        if labels.remove(&bottom).is_some() {
+            self.store.set_labels(&stack_name, &labels)?;
+        }
+
+        info(&format!("Pulling {}...", main));
+        self.git.checkout(&main).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        self.git.pull().map_err(|e| {
+            error(&e);
+            e
+        })?;
+
+        if !remaining.is_empty() {
+            let rebase_options = self.rebase_options(&args.rebase_options);
+            if !self.restack_onto(&main, &old_tip, &remaining, &rebase_options, args.yes, true)? {
+                return Ok(());
+            }
+            let recorded = self.store.get_pull_requests()?;
+            if let Err(e) = self.refetch_stack_sections(&remaining, &recorded) {
+                warning(&format!("Could not update pull request descriptions: {}", e));
+            }
+        }
+
+        success(&format!("Landed {}. The local branch is kept; delete it with `git branch -D {}`", bottom, bottom));
         Ok(())
     }
 
-    pub fn push(&self, args: PushArgs) -> Result<(), StackError> {
-        if !self.git.check_branch_exists(&args.branch).map_err(|e| {
-            error(&e);
-            e
-        })? {
-            let err = StackError::Invalid(format!("Branch {} does not exist.", args.branch));
+    // Follows pull request bases from `number` down to `base_branch` and
+    // returns the chain bottom first
+    fn pull_request_chain(&self, forge: &dyn Forge, number: u64, base_branch: &str) -> Result<Vec<PullRequest>, StackError> {
+        let mut chain = vec![forge.get_pull_request(number)?];
+        loop {
+            let base = chain.last().map(|pr| pr.base.clone()).unwrap_or_default();
+            if base == base_branch {
+                break;
+            }
+            if chain.iter().any(|pr| pr.head == base) {
+                return Err(StackError::Invalid(format!("Pull request bases form a cycle at {}", base)));
+            }
+            let pr = forge.find_pull_request(&base)?.ok_or_else(|| StackError::NotFound(format!(
+                "No open pull request for {}, which is neither {} nor the head of another pull request",
+                base, base_branch
+            )))?;
+            chain.push(pr);
+        }
+        chain.reverse();
+        Ok(chain)
+    }
+
+    pub fn fetch_pr(&self, args: FetchPrArgs) -> Result<(), StackError> {
+        let stack_name = args.name.unwrap_or_else(|| format!("pr-{}", args.number));
+        if self.store.get_stacks()?.contains(&stack_name) {
+            let err = StackError::Invalid(format!("Stack {} already exists. Pick another with --name.", stack_name));
             error(&err);
             return Err(err);
         }
-
-        let current_stack = self.store.get_current_stack_path().map_err(|e| {
+        let forge = self.forge().map_err(|e| {
+            error(&e);
+            e
+        })?;
+        // The new stack does not inherit the checked-out stack's base
+        let base_branch = args.base.clone().unwrap_or_else(|| self.repo_config.MAIN_BRANCH_NAME.clone());
+        let chain = self.pull_request_chain(forge.as_ref(), args.number, &base_branch).map_err(|e| {
             error(&e);
             e
         })?;
+        let branches: Vec<String> = chain.iter().map(|pr| pr.head.clone()).collect();
+        info(&format!("Found {} pull request(s): {}", chain.len(), branches.join(" -> ")));
 
-        let stack_contents = self.store.get_stack_contents(&current_stack).map_err(|e| {
+        let remote = &self.config.REMOTE_NAME;
+        self.git.fetch_branches(remote, &branches).map_err(|e| {
             error(&e);
             e
         })?;
-        if stack_contents.contains(&args.branch) {
-            let err = StackError::Invalid(format!("Branch {} already in stack.", args.branch));
-            error(&err);
-            return Err(err);
+        let current_branch = self.git.current_branch().unwrap_or(None);
+        for branch in &branches {
+            let upstream = format!("{}/{}", remote, branch);
+            if !self.git.check_branch_exists(branch)? {
+                self.git.create_tracking_branch(branch, &upstream).map_err(|e| {
+                    error(&e);
+                    e
+                })?;
+            } else if self.git.rev_parse(branch)? == self.git.rev_parse(&upstream)? {
+                continue;
+            } else if current_branch.as_deref() != Some(branch.as_str()) && self.git.is_ancestor(branch, &upstream)? {
+                self.git.set_branch(branch, &upstream).map_err(|e| {
+                    error(&e);
+                    e
+                })?;
+            } else {
+                warning(&format!("Kept local {}, which differs from {}", branch, upstream));
+            }
         }
 
-        self.store.push_to_stack(&args.branch).map_err(|e| {
+        self.store.create_stack(&stack_name).map_err(|e| {
             error(&e);
             e
         })?;
-        success(&format!("Pushed branch {} to stack {}", args.branch, current_stack));
+        self.store.set_current_stack(&stack_name)?;
+        self.store.set_stack_contents(&branches)?;
+        if let Some(base) = &args.base {
+            self.store.set_stack_setting(&stack_name, "MAIN_BRANCH_NAME", Some(base))?;
+        }
+        for pr in &chain {
+            self.store.set_pull_request(&pr.head, Some(pr.number))?;
+        }
+        success(&format!("Created stack {} from PR #{}", stack_name, args.number));
+        show_stack(&branches, current_branch.as_deref(), &HashMap::new(), &HashMap::new());
         Ok(())
     }
 
@@ -135,7 +1810,64 @@ impl StackManager {
         Ok(())
     }
 
-    pub fn status(&self, _args: StatusArgs) -> Result<(), StackError> {
+    // Diffstat of every branch against its parent, flagging branches over MAX_BRANCH_LINES
+    fn branch_stats(&self, contents: &[String]) -> Result<HashMap<String, String>, StackError> {
+        let mut details = HashMap::new();
+        for (index, branch) in contents.iter().enumerate() {
+            let parent = self.parent_branch(contents, index);
+            let stat = self.git.diff_stat(&parent, branch)?;
+            let mut detail = format!(
+                "{} file(s), {} {}",
+                stat.files,
+                format!("+{}", stat.insertions).green(),
+                format!("-{}", stat.deletions).red()
+            );
+            let max = self.config.MAX_BRANCH_LINES;
+            if max > 0 && stat.insertions + stat.deletions > max {
+                detail.push_str(&format!(" {}", format!("[over {} lines]", max).yellow().bold()));
+            }
+            details.insert(branch.clone(), detail);
+        }
+        Ok(details)
+    }
+
+    // Pull request state of every submitted branch, served from the status
+    // cache while younger than REMOTE_STATUS_TTL
+    fn remote_statuses(&self, contents: &[String], refresh: bool) -> Result<HashMap<String, String>, StackError> {
+        let pull_requests = self.store.get_pull_requests()?;
+        let mut cache = self.store.get_status_cache()?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        let mut forge = None;
+        let mut details = HashMap::new();
+        for branch in contents {
+            let Some(&number) = pull_requests.get(branch) else {
+                details.insert(branch.clone(), "no pull request".dimmed().to_string());
+                continue;
+            };
+            let cached = cache.get(&number)
+                .filter(|(fetched_at, _)| !refresh && now.saturating_sub(*fetched_at) < self.config.REMOTE_STATUS_TTL)
+                .and_then(|(_, status)| PullRequestStatus::from_cache_string(status));
+            let status = match cached {
+                Some(status) => status,
+                None => {
+                    if forge.is_none() {
+                        forge = Some(self.forge()?);
+                    }
+                    let status = forge.as_ref().unwrap().get_status(number)?;
+                    cache.insert(number, (now, status.to_cache_string()));
+                    status
+                }
+            };
+            details.insert(branch.clone(), format_pull_request_status(number, &status));
+        }
+        self.store.set_status_cache(&cache)?;
+        Ok(details)
+    }
+
+    pub fn status(&self, args: StatusArgs) -> Result<(), StackError> {
         let current_stack = self.store.get_current_stack_path().map_err(|e| {
             error(&e);
             e
@@ -145,7 +1877,130 @@ impl StackManager {
             error(&e);
             e
         })?;
-        show_stack(&stack_contents);
+        let current_branch = self.git.current_branch().unwrap_or(None);
+        let labels = self.store.get_labels(&current_stack).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let mut details = if args.stat {
+            self.branch_stats(&stack_contents).map_err(|e| {
+                error(&e);
+                e
+            })?
+        } else {
+            HashMap::new()
+        };
+        if args.remote {
+            let statuses = self.remote_statuses(&stack_contents, args.refresh).map_err(|e| {
+                error(&e);
+                e
+            })?;
+            for (branch, status) in statuses {
+                details.entry(branch)
+                    .and_modify(|detail| *detail = format!("{}  {}", detail, status))
+                    .or_insert(status);
+            }
+        }
+        show_stack(&stack_contents, current_branch.as_deref(), &labels, &details);
+        self.warn_if_head_elsewhere(&current_stack, current_branch.as_deref()).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        Ok(())
+    }
+
+    pub fn log(&self, _args: LogArgs) -> Result<(), StackError> {
+        let contents = self.current_stack_contents().map_err(|e| {
+            error(&e);
+            e
+        })?;
+        if contents.is_empty() {
+            info("Stack is empty");
+            return Ok(());
+        }
+        let current_branch = self.git.current_branch().unwrap_or(None);
+
+        // Top of the stack first, like `git log`
+        for (index, branch) in contents.iter().enumerate().rev() {
+            let parent = self.parent_branch(&contents, index);
+            let commits = self.git.log(&parent, branch).map_err(|e| {
+                error(&e);
+                e
+            })?;
+
+            let mut notes = Vec::new();
+            if commits.is_empty() {
+                notes.push("no unique commits".to_string());
+            }
+            if !self.git.is_ancestor(&parent, branch)? {
+                notes.push(format!("needs restack onto {}", parent));
+            }
+            show_branch_log(index, branch, current_branch.as_deref() == Some(branch.as_str()), &notes, &commits);
+        }
+        println!("  {}", self.config.MAIN_BRANCH_NAME);
+        Ok(())
+    }
+
+    pub fn diff(&self, args: DiffArgs) -> Result<(), StackError> {
+        let contents = self.current_stack_contents().map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let target = self.resolve_branch(args.target).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let index = self.resolve_index(&contents, &target).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let parent = self.parent_branch(&contents, index);
+
+        self.git.show_diff(&parent, &contents[index], args.stat).map_err(|e| {
+            error(&e);
+            e
+        })?;
+
+        let max = self.config.MAX_BRANCH_LINES;
+        if max > 0 {
+            let stat = self.git.diff_stat(&parent, &contents[index]).map_err(|e| {
+                error(&e);
+                e
+            })?;
+            if stat.insertions + stat.deletions > max {
+                warning(&format!(
+                    "{} changes {} lines, over the limit of {}",
+                    contents[index],
+                    stat.insertions + stat.deletions,
+                    max
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn which(&self, args: WhichArgs) -> Result<(), StackError> {
+        let branch = self.resolve_branch(args.branch).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let stacks = self.store.find_stacks_with_branch(&branch).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        if stacks.is_empty() {
+            info(&format!("Branch {} is not in any stack", branch));
+            return Ok(());
+        }
+
+        let current_stack = self.store.get_current_stack_path().unwrap_or_default();
+        for (stack, index) in &stacks {
+            let marker = if *stack == current_stack { " (current)" } else { "" };
+            info(&format!("{}: [{}] in stack {}{}", branch, index, stack, marker));
+        }
+        if !current_stack.is_empty() && !stacks.iter().any(|(stack, _)| *stack == current_stack) {
+            warning(&format!("Branch {} is not in the current stack {}", branch, current_stack));
+        }
         Ok(())
     }
 
@@ -165,6 +2020,7 @@ impl StackManager {
             return Ok(());
         }
 
+        let rebase_options = self.rebase_options(&args.rebase_options);
         let last_index = stack_contents.len() - 1;
         let from = args.from.unwrap_or(0).min(last_index);
         let to = args.to.unwrap_or(last_index).min(last_index);
@@ -188,7 +2044,7 @@ impl StackManager {
                     e
                 })?;
                 info("Rebasing...");
-                self.git.rebase_onto(&stack_contents[0], &self.config.MAIN_BRANCH_NAME).map_err(|e| {
+                self.git.rebase_onto(&stack_contents[0], &self.config.MAIN_BRANCH_NAME, &rebase_options).map_err(|e| {
                     error(&e);
                     e
                 })?;
@@ -210,39 +2066,23 @@ impl StackManager {
             }
         }
 
-        for window in stack_contents[from..=to].windows(2) {
-            let base_branch = &window[0];
-            let target_branch = &window[1];
-            
-            let (accept, continue_op) = self.configured_confirmation(
-                &format!("Rebase {} onto {}?", target_branch, base_branch),
-                self.config.CONFIRMATION_ON_GIT_REBASE,
-                args.yes
-            )?;
-
-            if !continue_op { return Ok(()); }
-            if accept {
-                info(&format!("Rebasing {} onto {}", target_branch, base_branch));
-                self.git.rebase_onto(target_branch, base_branch).map_err(|e| {
+        if let Some(old_tip) = &args.old_tip {
+            // The branch at `from` was already rewritten, e.g. amended by hand
+            let old_tip = self.git.rev_parse(old_tip).map_err(|e| {
+                error(&e);
+                e
+            })?;
+            if from < to {
+                if !self.restack_onto(&stack_contents[from], &old_tip, &stack_contents[from + 1..=to], &rebase_options, args.yes, true)? {
+                    return Ok(());
+                }
+                self.git.checkout(&stack_contents[from]).map_err(|e| {
                     error(&e);
                     e
                 })?;
-
-                let (accept, continue_op) = self.configured_confirmation(
-                    &format!("Push changes to {}?", target_branch),
-                    self.config.CONFIRMATION_ON_GIT_PUSH,
-                    args.yes
-                )?;
-
-                if !continue_op { return Ok(()); }
-                if accept {
-                    info(&format!("Pushing changes to {}", target_branch));
-                    self.git.push(true).map_err(|e| {
-                        error(&e);
-                        e
-                    })?;
-                }
             }
+        } else if !self.restack(&stack_contents[from..=to], &rebase_options, args.yes, true)? {
+            return Ok(());
         }
 
         success("Stack rebased successfully");
@@ -276,8 +2116,59 @@ impl StackManager {
         Ok(())
     }
 
+    fn show_config_entries(&self, show_origin: bool) {
+        let entries: Vec<(String, String, String)> = self.config.entries().into_iter()
+            .map(|(key, value)| {
+                let origin = if show_origin { self.config.origin(&key).to_string() } else { String::new() };
+                (key, value, origin)
+            })
+            .collect();
+        show_config(&entries);
+    }
+
+    fn check_stack_key(key: &str) -> Result<(), StackError> {
+        if !key.starts_with("PR_") && key != "MAIN_BRANCH_NAME" {
+            return Err(StackError::Invalid(format!("{} cannot be set per stack. Only MAIN_BRANCH_NAME and PR_* settings can.", key)));
+        }
+        Ok(())
+    }
+
     pub fn config(&self, args: ConfigArgs) -> Result<(), StackError> {
-        let parts: Vec<&str> = args.setting.splitn(2, '=').collect();
+        match args.command {
+            Some(ConfigCommand::Get(get_args)) => {
+                let value = self.config.get(&get_args.key).map_err(|e| {
+                    error(&e);
+                    e
+                })?;
+                println!("{}", value);
+                return Ok(());
+            }
+            Some(ConfigCommand::List(list_args)) => {
+                self.show_config_entries(list_args.show_origin);
+                return Ok(());
+            }
+            Some(ConfigCommand::Unset(unset_args)) => return self.config_unset(&unset_args.key, unset_args.stack),
+            None => {}
+        }
+        if args.help_keys {
+            let defaults = Config::new();
+            let keys: Vec<(String, String, String, String)> = CONFIG_KEYS.iter()
+                .map(|key| (
+                    key.name.to_string(),
+                    key.value_type.describe(),
+                    defaults.get(key.name).unwrap_or_default(),
+                    key.description.to_string(),
+                ))
+                .collect();
+            show_config_keys(&keys);
+            return Ok(());
+        }
+        if args.show_origin {
+            self.show_config_entries(true);
+            return Ok(());
+        }
+        let setting = args.setting.unwrap_or_default();
+        let parts: Vec<&str> = setting.splitn(2, '=').collect();
         if parts.len() != 2 {
             let err = StackError::Invalid("Config setting must be in KEY=VALUE format".to_string());
             error(&err);
@@ -285,9 +2176,29 @@ impl StackManager {
         }
 
         let key = parts[0].trim();
-        let value = parts[1].trim();
+        let value = validate(key, parts[1].trim()).map_err(|e| {
+            error(&e);
+            e
+        })?;
+
+        if args.stack {
+            Self::check_stack_key(key).map_err(|e| {
+                error(&e);
+                e
+            })?;
+            let current_stack = self.store.get_current_stack_path().map_err(|e| {
+                error(&e);
+                e
+            })?;
+            self.store.set_stack_setting(&current_stack, key, Some(&value)).map_err(|e| {
+                error(&e);
+                e
+            })?;
+            success(&format!("Updated config of stack {} with {} = {}", current_stack, key, value));
+            return Ok(());
+        }
 
-        self.store.update_config(key, value).map_err(|e| {
+        self.store.update_config(key, &value).map_err(|e| {
             error(&e);
             e
         })?;
@@ -295,19 +2206,185 @@ impl StackManager {
         success(&format!("Updated config with {} = {}", key, value));
         Ok(())
     }
+
+    fn config_unset(&self, key: &str, stack: bool) -> Result<(), StackError> {
+        config_key(key).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        if stack {
+            Self::check_stack_key(key).map_err(|e| {
+                error(&e);
+                e
+            })?;
+            let current_stack = self.store.get_current_stack_path().map_err(|e| {
+                error(&e);
+                e
+            })?;
+            if !self.store.get_stack_settings(&current_stack)?.contains_key(key) {
+                info(&format!("{} is not set for stack {}", key, current_stack));
+                return Ok(());
+            }
+            self.store.set_stack_setting(&current_stack, key, None).map_err(|e| {
+                error(&e);
+                e
+            })?;
+            success(&format!("Unset {} for stack {}", key, current_stack));
+            return Ok(());
+        }
+        let removed = self.store.unset_config(key).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        if removed {
+            success(&format!("Unset {}", key));
+        } else {
+            info(&format!("{} is not set in the repository config", key));
+        }
+        Ok(())
+    }
+
+    pub fn hooks_install(&self) -> Result<(), StackError> {
+        let hooks_dir = self.git.hooks_dir().map_err(|e| {
+            error(&e);
+            e
+        })?;
+        for hook in HOOKS {
+            let chained = install_hook(&hooks_dir, hook).map_err(|e| {
+                error(&e);
+                e
+            })?;
+            if chained {
+                info(&format!("Existing {} hook will run before stack's", hook));
+            }
+        }
+        success(&format!("Installed git hooks in {}", hooks_dir.display()));
+        Ok(())
+    }
+}
+
+fn post_checkout(store: &FsStore, git: &Git, args: PostCheckoutArgs) -> Result<(), StackError> {
+    // git passes 1 for branch checkouts and 0 for file checkouts
+    if args.branch_checkout != "1" {
+        return Ok(());
+    }
+    let Some(branch) = git.current_branch()? else {
+        return Ok(());
+    };
+
+    let current_stack = store.get_current_stack_path().unwrap_or_default();
+    let stacks = store.find_stacks_with_branch(&branch)?;
+    if stacks.is_empty() || stacks.iter().any(|(stack, _)| *stack == current_stack) {
+        return Ok(());
+    }
+
+    let (stack, _) = &stacks[0];
+    store.set_current_stack(stack)?;
+    info(&format!("Switched to stack {} (contains {})", stack, branch));
+    if stacks.len() > 1 {
+        let others: Vec<&str> = stacks[1..].iter().map(|(stack, _)| stack.as_str()).collect();
+        warning(&format!("{} is also in stack(s): {}", branch, others.join(", ")));
+    }
+    Ok(())
+}
+
+fn post_rewrite(store: &FsStore, git: &Git, _args: PostRewriteArgs) -> Result<(), StackError> {
+    let Some(branch) = git.current_branch()? else {
+        return Ok(());
+    };
+
+    // Git passes "<old-sha> <new-sha>" for every rewritten commit on stdin
+    let tip = git.rev_parse(&branch)?;
+    let old_tip = std::io::stdin().lines()
+        .map_while(Result::ok)
+        .find_map(|line| {
+            let mut shas = line.split_whitespace();
+            let old = shas.next()?;
+            (shas.next()? == tip).then(|| old.to_string())
+        });
+
+    for (stack, index) in store.find_stacks_with_branch(&branch)? {
+        let contents = store.get_stack_contents(&stack)?;
+        if index + 1 < contents.len() {
+            warning(&format!(
+                "{} was rewritten; its descendants in stack {} need restacking: {}",
+                branch,
+                stack,
+                contents[index + 1..].join(", ")
+            ));
+            // Replaying from the old tip skips the commits that were just rewritten
+            if let Some(old_tip) = &old_tip {
+                info(&format!("Run `stack rebase --from {} --old-tip {}` on stack {}", index, old_tip, stack));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_hook(current_dir: &std::path::Path, command: HooksCommand) -> Result<(), StackError> {
+    // Restacks driven by stack itself would otherwise trigger these on every step
+    if std::env::var_os(INTERNAL_ENV).is_some() {
+        return Ok(());
+    }
+    let Ok(store) = FsStore::find(current_dir) else {
+        return Ok(());
+    };
+    let git = Git::new();
+    match command {
+        HooksCommand::PostCheckout(args) => post_checkout(&store, &git, args),
+        HooksCommand::PostRewrite(args) => post_rewrite(&store, &git, args),
+        HooksCommand::Install(_) => unreachable!(),
+    }
+}
+
+// .stack goes at the top of the work tree, whatever directory init runs in
+fn init_repository(args: InitArgs, config_flags: &[String]) -> Result<(), StackError> {
+    let git = Git::new();
+    let root = git.toplevel()?;
+    let mut config = user_config()?;
+    config.apply_env()?;
+    config.apply_flags(config_flags)?;
+    let detected_main = git.default_branch(&config.REMOTE_NAME)?;
+    init(&root, args.main, detected_main, args.yes)
 }
 
-pub fn execute(cmd: Commands) -> Result<(), StackError> {
+pub fn execute(cmd: Commands, config_flags: &[String]) -> Result<(), StackError> {
     let current_dir = std::env::current_dir()?;
     
-    if let Commands::Init(_) = cmd {
-        init(&current_dir);
-        Ok(())
+    if let Commands::Init(args) = cmd {
+        init_repository(args, config_flags).map_err(|e| {
+            error(&e);
+            e
+        })
+    } else if let Commands::Hooks(HooksArgs { command: command @ (HooksCommand::PostCheckout(_) | HooksCommand::PostRewrite(_)) }) = cmd {
+        run_hook(&current_dir, command)
     } else {
-        let store = FsStore::new(&current_dir)?;
+        // `config get` prints only the value, so that scripts can capture it
+        let store = if matches!(cmd, Commands::Config(ConfigArgs { command: Some(ConfigCommand::Get(_)), .. })) {
+            FsStore::find(&current_dir).map_err(|e| {
+                error(&e);
+                e
+            })?
+        } else {
+            FsStore::new(&current_dir)?
+        };
         let git = Git::new();
-        let config = store.read_config_file()?;
-        let manager = StackManager::new(store, git, config)?;
+        let mut config = store.read_config_file().map_err(|e| {
+            error(&e);
+            e
+        })?;
+        config.apply_env().map_err(|e| {
+            error(&e);
+            e
+        })?;
+        config.apply_flags(config_flags).map_err(|e| {
+            error(&e);
+            e
+        })?;
+        let manager = StackManager::new(store, git, config).map_err(|e| {
+            error(&e);
+            e
+        })?;
         match cmd {
             Commands::Init(_) => unreachable!(),
             Commands::Checkout(args) => {
@@ -319,6 +2396,36 @@ pub fn execute(cmd: Commands) -> Result<(), StackError> {
             Commands::Push(args) => {
                 manager.push(args)
             }
+            Commands::Create(args) => {
+                manager.create(args)
+            }
+            Commands::Split(args) => {
+                manager.split(args)
+            }
+            Commands::Fold(args) => {
+                manager.fold(args)
+            }
+            Commands::Move(args) => {
+                manager.move_branch(args)
+            }
+            Commands::Modify(args) => {
+                manager.modify(args)
+            }
+            Commands::Absorb(args) => {
+                manager.absorb(args)
+            }
+            Commands::Edit(args) => {
+                manager.edit(args)
+            }
+            Commands::Submit(args) => {
+                manager.submit(args)
+            }
+            Commands::Land(args) => {
+                manager.land(args)
+            }
+            Commands::FetchPr(args) => {
+                manager.fetch_pr(args)
+            }
             Commands::Pop(args) => {
                 manager.pop(args)
             }
@@ -331,6 +2438,30 @@ pub fn execute(cmd: Commands) -> Result<(), StackError> {
             Commands::Status(args) => {
                 manager.status(args)
             }
+            Commands::Which(args) => {
+                manager.which(args)
+            }
+            Commands::Log(args) => {
+                manager.log(args)
+            }
+            Commands::Diff(args) => {
+                manager.diff(args)
+            }
+            Commands::Up(args) => {
+                manager.up(args)
+            }
+            Commands::Down(args) => {
+                manager.down(args)
+            }
+            Commands::Top(args) => {
+                manager.top(args)
+            }
+            Commands::Bottom(args) => {
+                manager.bottom(args)
+            }
+            Commands::Goto(args) => {
+                manager.goto(args)
+            }
             Commands::Rebase(args) => {
                 manager.rebase(args)
             }
@@ -343,6 +2474,10 @@ pub fn execute(cmd: Commands) -> Result<(), StackError> {
             Commands::Config(args) => {
                 manager.config(args)
             }
+            Commands::Hooks(args) => match args.command {
+                HooksCommand::Install(_) => manager.hooks_install(),
+                _ => unreachable!(),
+            }
         }
     }
 }
\ No newline at end of file
@@ -1,2 +1,3 @@
 pub mod cmd;
-pub mod args;
\ No newline at end of file
+pub mod args;
+pub mod todo;
\ No newline at end of file
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use crate::error::StackError;
+
+// The `stack edit` todo list, modelled on `git rebase -i`.
+
+#[derive(Debug, PartialEq)]
+pub enum TodoAction {
+    Pick,
+    Drop,
+    Fold,
+    Split,
+    Rename(String),
+    Label(String),
+}
+
+#[derive(Debug)]
+pub struct TodoLine {
+    pub action: TodoAction,
+    pub branch: String,
+}
+
+const HELP: &str = "
+# Edit the stack, bottom first. Lines can be reordered, and on save the
+# branches are restacked to match.
+#
+# Commands:
+# p, pick <branch> = keep the branch
+# r, rename <branch> <new-name> = keep the branch under a new name
+# l, label <branch> [<text>] = keep the branch and set (or clear) its label
+# f, fold <branch> = merge the branch into the branch on the line before it
+# s, split <branch> = split the branch once the stack is restacked
+# d, drop <branch> = remove the branch and drop its commits from the stack
+#
+# Removing a line is the same as drop; the git branch itself is kept.
+# Emptying the list aborts the edit.
+";
+
+pub fn render(branches: &[String], labels: &HashMap<String, String>) -> String {
+    let mut todo = String::new();
+    for branch in branches {
+        match labels.get(branch) {
+            Some(label) => todo.push_str(&format!("pick {} # {}\n", branch, label)),
+            None => todo.push_str(&format!("pick {}\n", branch)),
+        }
+    }
+    todo.push_str(HELP);
+    todo
+}
+
+pub fn parse(text: &str, branches: &[String]) -> Result<Vec<TodoLine>, StackError> {
+    let mut lines: Vec<TodoLine> = Vec::new();
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let mut parts = line.split_whitespace();
+        let verb = parts.next().unwrap_or_default();
+        let Some(branch) = parts.next() else {
+            return Err(StackError::Invalid(format!("Missing branch name: {}", line)));
+        };
+        let rest: Vec<&str> = parts.collect();
+
+        let action = match verb {
+            "p" | "pick" => TodoAction::Pick,
+            "d" | "drop" => TodoAction::Drop,
+            "f" | "fold" => TodoAction::Fold,
+            "s" | "split" => TodoAction::Split,
+            "r" | "rename" => match rest.first() {
+                Some(name) => TodoAction::Rename(name.to_string()),
+                None => return Err(StackError::Invalid(format!("rename needs a new name: {}", line))),
+            },
+            "l" | "label" => TodoAction::Label(rest.join(" ")),
+            _ => return Err(StackError::Invalid(format!("Unknown command: {}", line))),
+        };
+
+        if !branches.iter().any(|b| b == branch) {
+            return Err(StackError::Invalid(format!("Branch {} is not in the stack", branch)));
+        }
+        if lines.iter().any(|l| l.branch == branch) {
+            return Err(StackError::Invalid(format!("Branch {} is listed more than once", branch)));
+        }
+        lines.push(TodoLine { action, branch: branch.to_string() });
+    }
+
+    let mut kept = lines.iter().filter(|l| l.action != TodoAction::Drop);
+    if kept.next().is_some_and(|first| first.action == TodoAction::Fold) {
+        return Err(StackError::Invalid("The first branch cannot be folded".to_string()));
+    }
+    for line in &lines {
+        if let TodoAction::Rename(name) = &line.action {
+            let clashes = lines.iter().any(|l| l.branch == *name)
+                || lines.iter().filter(|l| l.action == TodoAction::Rename(name.clone())).count() > 1;
+            if clashes {
+                return Err(StackError::Invalid(format!("Cannot rename {} to {}: name is taken", line.branch, name)));
+            }
+        }
+    }
+    Ok(lines)
+}
@@ -1,11 +1,149 @@
 
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
 use crate::error::StackError;
+use super::file::parse_config_file;
+
+/// Where an effective config value came from. Later layers win: defaults,
+/// the user config, the repository config, `STACK_*` variables, then
+/// `--config` flags. Settings of a stack override the files only.
+#[derive(Clone)]
+pub enum ConfigOrigin {
+    Default,
+    File(PathBuf),
+    Env(String),
+    Flag,
+    Stack(String),
+}
+
+impl fmt::Display for ConfigOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Env(name) => write!(f, "env {}", name),
+            Self::Flag => write!(f, "--config flag"),
+            Self::Stack(name) => write!(f, "stack {}", name),
+        }
+    }
+}
+
+/// The type of a config value, checked whenever one is set.
+#[derive(Clone, Copy)]
+pub enum ValueType {
+    Bool,
+    Number,
+    Text,
+    // Empty is allowed where listed
+    OneOf(&'static [&'static str]),
+}
+
+impl ValueType {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Bool => "true or false".to_string(),
+            Self::Number => "a whole number".to_string(),
+            Self::Text => "text".to_string(),
+            Self::OneOf(choices) => {
+                let choices: Vec<&str> = choices.iter()
+                    .map(|choice| if choice.is_empty() { "\"\"" } else { choice })
+                    .collect();
+                format!("one of {}", choices.join(", "))
+            }
+        }
+    }
+
+    // Normalized value, or None if it is not of this type
+    fn parse(&self, value: &str) -> Option<String> {
+        match self {
+            // 1 and 0 are what older versions wrote
+            Self::Bool => match value {
+                "true" | "1" => Some("true".to_string()),
+                "false" | "0" => Some("false".to_string()),
+                _ => None,
+            },
+            Self::Number => value.parse::<u64>().ok().map(|number| number.to_string()),
+            Self::Text => Some(value.to_string()),
+            Self::OneOf(choices) => choices.iter()
+                .find(|choice| choice.eq_ignore_ascii_case(value))
+                .map(|choice| choice.to_string()),
+        }
+    }
+}
+
+pub struct ConfigKey {
+    pub name: &'static str,
+    pub value_type: ValueType,
+    pub description: &'static str,
+}
+
+pub const CONFIG_KEYS: &[ConfigKey] = &[
+    ConfigKey { name: "MAIN_BRANCH_NAME", value_type: ValueType::Text, description: "Branch that stacks are based on" },
+    ConfigKey { name: "CONFIRMATION_ON_GIT_PUSH", value_type: ValueType::Bool, description: "Ask before every push" },
+    ConfigKey { name: "CONFIRMATION_ON_GIT_REBASE", value_type: ValueType::Bool, description: "Ask before every rebase" },
+    ConfigKey { name: "REBASE_GPG_SIGN", value_type: ValueType::Bool, description: "Sign rebased commits (--gpg-sign)" },
+    ConfigKey { name: "REBASE_NO_VERIFY", value_type: ValueType::Bool, description: "Skip hooks while rebasing (--no-verify)" },
+    ConfigKey { name: "REBASE_COMMITTER_DATE_IS_AUTHOR_DATE", value_type: ValueType::Bool, description: "Keep commit dates stable across rebases" },
+    ConfigKey { name: "REBASE_STRATEGY_OPTION", value_type: ValueType::Text, description: "Merge strategy option passed as -X, e.g. patience" },
+    ConfigKey { name: "REBASE_MERGES", value_type: ValueType::Bool, description: "Keep merge commits when rebasing (--rebase-merges)" },
+    ConfigKey { name: "FOLD_DELETE_BRANCH", value_type: ValueType::Bool, description: "Delete a branch after folding it into its parent" },
+    ConfigKey { name: "MAX_BRANCH_LINES", value_type: ValueType::Number, description: "Flag branches changing more lines than this in status --stat (0 disables)" },
+    ConfigKey { name: "REMOTE_NAME", value_type: ValueType::Text, description: "Remote that branches are pushed to" },
+    ConfigKey { name: "FORGE", value_type: ValueType::OneOf(&["", "github", "gitlab"]), description: "Forge hosting pull requests, detected from the remote when empty" },
+    ConfigKey { name: "FORGE_API_URL", value_type: ValueType::Text, description: "Forge API base URL, derived from the remote when empty" },
+    ConfigKey { name: "FORGE_REPOSITORY", value_type: ValueType::Text, description: "owner/name of the repository, derived from the remote when empty" },
+    ConfigKey { name: "REMOTE_STATUS_TTL", value_type: ValueType::Number, description: "Seconds before cached pull request statuses are fetched again" },
+    ConfigKey { name: "LAND_MERGE_METHOD", value_type: ValueType::OneOf(&["merge", "squash", "rebase"]), description: "How stack land merges pull requests" },
+    ConfigKey { name: "PR_DRAFT", value_type: ValueType::Bool, description: "Open new pull requests as drafts" },
+    ConfigKey { name: "PR_REVIEWERS", value_type: ValueType::Text, description: "Comma separated reviewers; teams as org/team" },
+    ConfigKey { name: "PR_LABELS", value_type: ValueType::Text, description: "Comma separated labels added to pull requests" },
+    ConfigKey { name: "PR_TITLE_TEMPLATE", value_type: ValueType::Text, description: "Pull request title; {subject}, {branch}, {stack}, {index} and {count} are replaced" },
+];
+
+pub fn config_key(name: &str) -> Result<&'static ConfigKey, StackError> {
+    CONFIG_KEYS.iter().find(|key| key.name == name).ok_or_else(|| StackError::Invalid(format!(
+        "Unknown config key {}. Run `stack config --help-keys` to list them.",
+        name
+    )))
+}
+
+/// Checks `value` against the key's type and returns it normalized.
+pub fn validate(name: &str, value: &str) -> Result<String, StackError> {
+    let key = config_key(name)?;
+    key.value_type.parse(value).ok_or_else(|| StackError::Invalid(format!(
+        "{} must be {}, not {:?}",
+        name,
+        key.value_type.describe(),
+        value
+    )))
+}
 
 #[allow(non_snake_case)]
+#[derive(Clone)]
 pub struct Config {
     pub MAIN_BRANCH_NAME: String,
     pub CONFIRMATION_ON_GIT_PUSH: bool,
     pub CONFIRMATION_ON_GIT_REBASE: bool,
+    pub REBASE_GPG_SIGN: bool,
+    pub REBASE_NO_VERIFY: bool,
+    pub REBASE_COMMITTER_DATE_IS_AUTHOR_DATE: bool,
+    pub REBASE_STRATEGY_OPTION: String,
+    pub REBASE_MERGES: bool,
+    pub FOLD_DELETE_BRANCH: bool,
+    pub MAX_BRANCH_LINES: usize,
+    pub REMOTE_NAME: String,
+    pub FORGE: String,
+    pub FORGE_API_URL: String,
+    pub FORGE_REPOSITORY: String,
+    pub REMOTE_STATUS_TTL: u64,
+    pub LAND_MERGE_METHOD: String,
+    pub PR_DRAFT: bool,
+    pub PR_REVIEWERS: String,
+    pub PR_LABELS: String,
+    pub PR_TITLE_TEMPLATE: String,
+    // Keys set by any layer other than the defaults
+    pub origins: HashMap<String, ConfigOrigin>,
 }
 
 impl Config {
@@ -15,52 +153,219 @@ impl Config {
             MAIN_BRANCH_NAME: "main".to_string(),
             CONFIRMATION_ON_GIT_PUSH: true,
             CONFIRMATION_ON_GIT_REBASE: true,
+            REBASE_GPG_SIGN: false,
+            REBASE_NO_VERIFY: false,
+            REBASE_COMMITTER_DATE_IS_AUTHOR_DATE: true,
+            REBASE_STRATEGY_OPTION: "".to_string(),
+            REBASE_MERGES: false,
+            FOLD_DELETE_BRANCH: true,
+            MAX_BRANCH_LINES: 0,
+            REMOTE_NAME: "origin".to_string(),
+            // github or gitlab, detected from the remote host when empty
+            FORGE: "".to_string(),
+            // Both derived from the remote URL when empty
+            FORGE_API_URL: "".to_string(),
+            FORGE_REPOSITORY: "".to_string(),
+            // Seconds before cached pull request statuses are fetched again
+            REMOTE_STATUS_TTL: 60,
+            // merge, squash or rebase
+            LAND_MERGE_METHOD: "merge".to_string(),
+            PR_DRAFT: false,
+            // Comma separated; teams as org/team
+            PR_REVIEWERS: "".to_string(),
+            PR_LABELS: "".to_string(),
+            PR_TITLE_TEMPLATE: "{subject}".to_string(),
+            origins: HashMap::new(),
         }
     }
 
-    pub fn to_string(&self) -> String {
-        format!("MAIN_BRANCH_NAME={}\nCONFIRMATION_ON_GIT_PUSH={}\nCONFIRMATION_ON_GIT_REBASE={}",
-            self.MAIN_BRANCH_NAME,
-            self.CONFIRMATION_ON_GIT_PUSH,
-            self.CONFIRMATION_ON_GIT_REBASE,
-        )
-    }
-
-    pub fn set_kv(&mut self, key: &str, value: &str) {
+    pub fn set_kv(&mut self, key: &str, value: &str) -> Result<(), StackError> {
+        let value = validate(key, value)?;
+        let value = value.as_str();
         match key {
             "MAIN_BRANCH_NAME" => {
                 self.MAIN_BRANCH_NAME = value.to_string();
             }
             "CONFIRMATION_ON_GIT_PUSH" => {
-                self.CONFIRMATION_ON_GIT_PUSH = value == "true" || value == "1";
+                self.CONFIRMATION_ON_GIT_PUSH = value == "true";
             }
             "CONFIRMATION_ON_GIT_REBASE" => {
-                self.CONFIRMATION_ON_GIT_REBASE = value == "true" || value == "1";
+                self.CONFIRMATION_ON_GIT_REBASE = value == "true";
+            }
+            "REBASE_GPG_SIGN" => {
+                self.REBASE_GPG_SIGN = value == "true";
+            }
+            "REBASE_NO_VERIFY" => {
+                self.REBASE_NO_VERIFY = value == "true";
+            }
+            "REBASE_COMMITTER_DATE_IS_AUTHOR_DATE" => {
+                self.REBASE_COMMITTER_DATE_IS_AUTHOR_DATE = value == "true";
+            }
+            "REBASE_STRATEGY_OPTION" => {
+                self.REBASE_STRATEGY_OPTION = value.to_string();
+            }
+            "REBASE_MERGES" => {
+                self.REBASE_MERGES = value == "true";
+            }
+            "FOLD_DELETE_BRANCH" => {
+                self.FOLD_DELETE_BRANCH = value == "true";
+            }
+            "MAX_BRANCH_LINES" => {
+                // 0 disables the size check
+                self.MAX_BRANCH_LINES = value.parse().unwrap_or_default();
+            }
+            "REMOTE_NAME" => {
+                self.REMOTE_NAME = value.to_string();
+            }
+            "FORGE" => {
+                self.FORGE = value.to_string();
+            }
+            "FORGE_API_URL" => {
+                self.FORGE_API_URL = value.to_string();
+            }
+            "FORGE_REPOSITORY" => {
+                self.FORGE_REPOSITORY = value.to_string();
+            }
+            "REMOTE_STATUS_TTL" => {
+                self.REMOTE_STATUS_TTL = value.parse().unwrap_or_default();
+            }
+            "LAND_MERGE_METHOD" => {
+                self.LAND_MERGE_METHOD = value.to_string();
+            }
+            "PR_DRAFT" => {
+                self.PR_DRAFT = value == "true";
+            }
+            "PR_REVIEWERS" => {
+                self.PR_REVIEWERS = value.to_string();
+            }
+            "PR_LABELS" => {
+                self.PR_LABELS = value.to_string();
+            }
+            "PR_TITLE_TEMPLATE" => {
+                self.PR_TITLE_TEMPLATE = value.to_string();
             }
             _ => {}
         }
+        Ok(())
     }
 
-    pub fn from_string(contents: String) -> Result<Self, StackError> {
-        let lines = contents.lines();
-        let mut config = Config::new();
+    // The current value of a key, formatted as it is written in config files
+    fn value(&self, key: &str) -> Option<String> {
+        let value = match key {
+            "MAIN_BRANCH_NAME" => self.MAIN_BRANCH_NAME.clone(),
+            "CONFIRMATION_ON_GIT_PUSH" => self.CONFIRMATION_ON_GIT_PUSH.to_string(),
+            "CONFIRMATION_ON_GIT_REBASE" => self.CONFIRMATION_ON_GIT_REBASE.to_string(),
+            "REBASE_GPG_SIGN" => self.REBASE_GPG_SIGN.to_string(),
+            "REBASE_NO_VERIFY" => self.REBASE_NO_VERIFY.to_string(),
+            "REBASE_COMMITTER_DATE_IS_AUTHOR_DATE" => self.REBASE_COMMITTER_DATE_IS_AUTHOR_DATE.to_string(),
+            "REBASE_STRATEGY_OPTION" => self.REBASE_STRATEGY_OPTION.clone(),
+            "REBASE_MERGES" => self.REBASE_MERGES.to_string(),
+            "FOLD_DELETE_BRANCH" => self.FOLD_DELETE_BRANCH.to_string(),
+            "MAX_BRANCH_LINES" => self.MAX_BRANCH_LINES.to_string(),
+            "REMOTE_NAME" => self.REMOTE_NAME.clone(),
+            "FORGE" => self.FORGE.clone(),
+            "FORGE_API_URL" => self.FORGE_API_URL.clone(),
+            "FORGE_REPOSITORY" => self.FORGE_REPOSITORY.clone(),
+            "REMOTE_STATUS_TTL" => self.REMOTE_STATUS_TTL.to_string(),
+            "LAND_MERGE_METHOD" => self.LAND_MERGE_METHOD.clone(),
+            "PR_DRAFT" => self.PR_DRAFT.to_string(),
+            "PR_REVIEWERS" => self.PR_REVIEWERS.clone(),
+            "PR_LABELS" => self.PR_LABELS.clone(),
+            "PR_TITLE_TEMPLATE" => self.PR_TITLE_TEMPLATE.clone(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Every key with its current value, in file order.
+    pub fn entries(&self) -> Vec<(String, String)> {
+        CONFIG_KEYS.iter()
+            .filter_map(|key| Some((key.name.to_string(), self.value(key.name)?)))
+            .collect()
+    }
+
+    pub fn origin(&self, key: &str) -> ConfigOrigin {
+        self.origins.get(key).cloned().unwrap_or(ConfigOrigin::Default)
+    }
+
+    pub fn get(&self, key: &str) -> Result<String, StackError> {
+        config_key(key)?;
+        Ok(self.value(key).unwrap_or_default())
+    }
+
+    /// Sets a key and records where the value came from.
+    pub fn apply(&mut self, key: &str, value: &str, origin: &ConfigOrigin) -> Result<(), StackError> {
+        self.set_kv(key, value).map_err(|e| match e {
+            StackError::Invalid(msg) => StackError::Invalid(format!("{} (in {})", msg, origin)),
+            e => e,
+        })?;
+        self.origins.insert(key.to_string(), origin.clone());
+        Ok(())
+    }
 
-        for line in lines {
-            if line.trim().is_empty() {
-                continue;
+    /// Applies the settings of a config file on top.
+    pub fn apply_file(&mut self, contents: &str, origin: &ConfigOrigin) -> Result<(), StackError> {
+        for (key, value) in parse_config_file(contents)? {
+            self.apply(&key, &value, origin)?;
+        }
+        Ok(())
+    }
+
+    /// Applies `STACK_<KEY>` environment variables on top.
+    pub fn apply_env(&mut self) -> Result<(), StackError> {
+        for key in CONFIG_KEYS {
+            let name = format!("STACK_{}", key.name);
+            if let Ok(value) = std::env::var(&name) {
+                self.apply(key.name, &value, &ConfigOrigin::Env(name))?;
             }
-            
-            let parts = line.splitn(2, '=').collect::<Vec<&str>>();
-            if parts.len() != 2 {
-                continue;
+        }
+        Ok(())
+    }
+
+    /// Applies the settings of a stack over those from the config files.
+    pub fn apply_stack_settings(&mut self, stack_name: &str, settings: &HashMap<String, String>) -> Result<(), StackError> {
+        let origin = ConfigOrigin::Stack(stack_name.to_string());
+        for (key, value) in settings {
+            if !matches!(self.origin(key), ConfigOrigin::Env(_) | ConfigOrigin::Flag) {
+                self.apply(key, value, &origin)?;
             }
-            
-            let key = parts[0].trim();
-            let value = parts[1].trim();
-            
-            config.set_kv(key, value);
         }
-        
-        Ok(config)
+        Ok(())
+    }
+
+    /// Applies `KEY=VALUE` settings given on the command line on top.
+    pub fn apply_flags(&mut self, settings: &[String]) -> Result<(), StackError> {
+        for setting in settings {
+            let (key, value) = setting.split_once('=').ok_or_else(|| {
+                StackError::Invalid(format!("--config {} must be in KEY=VALUE format", setting))
+            })?;
+            self.apply(key.trim(), value.trim(), &ConfigOrigin::Flag)?;
+        }
+        Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_key_has_a_value() {
+        let config = Config::new();
+        let names: Vec<String> = config.entries().into_iter().map(|(name, _)| name).collect();
+        let keys: Vec<&str> = CONFIG_KEYS.iter().map(|key| key.name).collect();
+        assert_eq!(names, keys);
+        assert_eq!(config.get("REMOTE_STATUS_TTL").unwrap(), "60");
+        assert!(config.get("NOT_A_KEY").is_err());
+    }
+
+    #[test]
+    fn values_are_returned_as_set() {
+        let mut config = Config::new();
+        config.apply_flags(&["PR_TITLE_TEMPLATE=[{stack}] a=b".to_string()]).unwrap();
+        config.apply("PR_LABELS", "one\ntwo", &ConfigOrigin::Flag).unwrap();
+        assert_eq!(config.get("PR_TITLE_TEMPLATE").unwrap(), "[{stack}] a=b");
+        assert_eq!(config.get("PR_LABELS").unwrap(), "one\ntwo");
+        assert!(config.entries().contains(&("PR_LABELS".to_string(), "one\ntwo".to_string())));
+    }
+}
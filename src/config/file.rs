@@ -0,0 +1,137 @@
+use crate::error::StackError;
+use super::config::{config_key, ValueType};
+
+// Config files are flat TOML: `KEY = value` lines and `#` comments. Files
+// written by older versions, with unquoted `KEY=VALUE` lines, read the same.
+
+fn unquote_basic(value: &str) -> Option<(String, &str)> {
+    let mut unquoted = String::new();
+    let mut chars = value.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Some((unquoted, &value[index + 1..])),
+            '\\' => match chars.next()?.1 {
+                'n' => unquoted.push('\n'),
+                't' => unquoted.push('\t'),
+                c => unquoted.push(c),
+            },
+            c => unquoted.push(c),
+        }
+    }
+    None
+}
+
+// A `#` only starts a comment after whitespace, so legacy values such as
+// `fix#1` keep theirs
+fn strip_comment(raw: &str) -> &str {
+    let mut previous = ' ';
+    for (index, c) in raw.char_indices() {
+        if c == '#' && previous.is_whitespace() {
+            return raw[..index].trim_end();
+        }
+        previous = c;
+    }
+    raw
+}
+
+fn parse_value(raw: &str) -> Option<String> {
+    let (value, rest) = if let Some(quoted) = raw.strip_prefix('"') {
+        unquote_basic(quoted)?
+    } else if let Some(quoted) = raw.strip_prefix('\'') {
+        let end = quoted.find('\'')?;
+        (quoted[..end].to_string(), &quoted[end + 1..])
+    } else {
+        return Some(strip_comment(raw).to_string());
+    };
+    let rest = rest.trim();
+    (rest.is_empty() || rest.starts_with('#')).then_some(value)
+}
+
+/// The settings in a config file, in order.
+pub fn parse_config_file(contents: &str) -> Result<Vec<(String, String)>, StackError> {
+    let mut settings = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || StackError::Invalid(format!("Cannot parse config line {}: {}", number + 1, line));
+        let (key, raw) = line.split_once('=').ok_or_else(invalid)?;
+        let value = parse_value(raw.trim()).ok_or_else(invalid)?;
+        settings.push((key.trim().to_string(), value));
+    }
+    Ok(settings)
+}
+
+fn quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
+
+/// Writes settings as commented TOML, each preceded by its description.
+pub fn render_config_file(settings: &[(String, String)]) -> String {
+    let mut contents = String::from("# stack configuration. Run `stack config --help-keys` for every key.\n");
+    for (name, value) in settings {
+        let key = config_key(name).ok();
+        if let Some(key) = key {
+            contents.push_str(&format!("\n# {}\n", key.description));
+        }
+        let value = match key.map(|key| key.value_type) {
+            Some(ValueType::Bool | ValueType::Number) => value.clone(),
+            _ => quote(value),
+        };
+        contents.push_str(&format!("{} = {}\n", name, value));
+    }
+    contents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> Vec<(String, String)> {
+        parse_config_file(contents).unwrap()
+    }
+
+    #[test]
+    fn strips_trailing_comments_from_bare_values() {
+        assert_eq!(
+            parse("CONFIRMATION_ON_GIT_PUSH = true # ask first\nPR_LABELS = fix#1"),
+            vec![
+                ("CONFIRMATION_ON_GIT_PUSH".to_string(), "true".to_string()),
+                ("PR_LABELS".to_string(), "fix#1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_quoted_and_legacy_values() {
+        assert_eq!(
+            parse("# header\nA = \"x # y\" # z\nB = 'lit\\eral'\nC=legacy value\nD="),
+            vec![
+                ("A".to_string(), "x # y".to_string()),
+                ("B".to_string(), "lit\\eral".to_string()),
+                ("C".to_string(), "legacy value".to_string()),
+                ("D".to_string(), "".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unterminated_strings() {
+        assert!(parse_config_file("A = \"open").is_err());
+    }
+
+    #[test]
+    fn render_round_trips() {
+        let settings = vec![
+            ("CONFIRMATION_ON_GIT_PUSH".to_string(), "false".to_string()),
+            ("PR_TITLE_TEMPLATE".to_string(), "[{stack}] \"{subject}\" # 1".to_string()),
+        ];
+        assert_eq!(parse(&render_config_file(&settings)), settings);
+    }
+}
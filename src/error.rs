@@ -1,6 +1,7 @@
 use std::fmt;
 use std::error::Error as StdError;
 use std::io;
+use crate::output::mask;
 
 #[derive(Debug)]
 pub enum StackError {
@@ -12,15 +13,19 @@ pub enum StackError {
     Invalid(String),
     // Not found errors
     NotFound(String),
+    // Forge (GitHub etc.) API errors
+    Forge(String),
 }
 
 impl fmt::Display for StackError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Forge tokens can end up in git or curl output
         match self {
-            Self::Io(err) => write!(f, "{}", err),
-            Self::Git(msg) => write!(f, "git error: {}", msg),
-            Self::Invalid(msg) => write!(f, "invalid: {}", msg),
-            Self::NotFound(msg) => write!(f, "not found: {}", msg),
+            Self::Io(err) => write!(f, "{}", mask(&err.to_string())),
+            Self::Git(msg) => write!(f, "git error: {}", mask(msg)),
+            Self::Invalid(msg) => write!(f, "invalid: {}", mask(msg)),
+            Self::NotFound(msg) => write!(f, "not found: {}", mask(msg)),
+            Self::Forge(msg) => write!(f, "forge error: {}", mask(msg)),
         }
     }
 }
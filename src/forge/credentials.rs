@@ -0,0 +1,229 @@
+use std::fs;
+use std::path::PathBuf;
+use crate::error::StackError;
+use crate::git::git::Git;
+use super::forge::ForgeKind;
+
+// Tokens are looked up in order: environment, git's credential helpers for
+// the forge host, then the config files of the forge's own CLI. They are
+// never written to .stack/config.
+
+fn token_variables(kind: ForgeKind) -> [&'static str; 2] {
+    match kind {
+        ForgeKind::GitHub => ["STACK_FORGE_TOKEN", "GITHUB_TOKEN"],
+        ForgeKind::GitLab => ["STACK_FORGE_TOKEN", "GITLAB_TOKEN"],
+    }
+}
+
+// `$override_var`, else `$XDG_CONFIG_HOME/<name>`, else `~/.config/<name>`
+fn cli_config_dir(override_var: &str, name: &str) -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os(override_var).filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir).join(name));
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config").join(name))
+}
+
+/// Finds `key` in the block of a YAML mapping keyed by `host`, which is how
+/// both `gh` (hosts.yml) and `glab` (config.yml) store per-host tokens.
+fn host_value(contents: &str, host: &str, key: &str) -> Option<String> {
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let mut lines = contents.lines();
+    let host_line = lines.find(|line| {
+        let name = line.trim().trim_end_matches(':');
+        line.trim().ends_with(':') && name.trim_matches(|c| c == '"' || c == '\'') == host
+    })?;
+    let host_indent = indent(host_line);
+    lines
+        .take_while(|line| line.trim().is_empty() || indent(line) > host_indent)
+        .find_map(|line| line.trim().strip_prefix(key)?.strip_prefix(':').map(str::trim))
+        .map(|value| value.trim_matches(|c| c == '"' || c == '\'').to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn cli_token(kind: ForgeKind, host: &str) -> Option<String> {
+    let (file, key) = match kind {
+        ForgeKind::GitHub => (cli_config_dir("GH_CONFIG_DIR", "gh")?.join("hosts.yml"), "oauth_token"),
+        ForgeKind::GitLab => (cli_config_dir("GLAB_CONFIG_DIR", "glab-cli")?.join("config.yml"), "token"),
+    };
+    let contents = fs::read_to_string(file).ok()?;
+    host_value(&contents, host, key)
+}
+
+pub fn resolve_token(git: &Git, kind: ForgeKind, host: &str) -> Result<String, StackError> {
+    find_token(kind, host, |host| git.credential_fill(host))
+}
+
+fn find_token(
+    kind: ForgeKind,
+    host: &str,
+    credential_fill: impl Fn(&str) -> Result<Option<String>, StackError>,
+) -> Result<String, StackError> {
+    if let Some(token) = token_variables(kind).iter()
+        .find_map(|name| std::env::var(name).ok().filter(|token| !token.is_empty()))
+    {
+        return Ok(token);
+    }
+    if let Some(token) = credential_fill(host)? {
+        return Ok(token);
+    }
+    cli_token(kind, host).ok_or_else(|| StackError::Invalid(format!(
+        "No forge token found for {}. Set STACK_FORGE_TOKEN, store one with a git credential helper, or log in with {}.",
+        host,
+        match kind {
+            ForgeKind::GitHub => "gh",
+            ForgeKind::GitLab => "glab",
+        }
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::{Mutex, MutexGuard};
+
+    // Tests here change the process environment
+    static ENV: Mutex<()> = Mutex::new(());
+
+    // Hosts no real git or CLI config will have credentials for
+    const GITHUB_HOST: &str = "github.stack.test";
+    const GITLAB_HOST: &str = "gitlab.stack.test";
+
+    const HOSTS_YML: &str = "\
+github.example.com:
+    user: someone
+    oauth_token: other-host-token
+github.com:
+    git_protocol: https
+    oauth_token: \"file-token\"
+";
+
+    // Holds ENV and puts back every variable it changed when dropped
+    struct EnvGuard {
+        saved: Vec<(&'static str, Option<OsString>)>,
+        _lock: MutexGuard<'static, ()>,
+    }
+
+    impl EnvGuard {
+        fn new() -> Self {
+            let lock = ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            Self { saved: Vec::new(), _lock: lock }
+        }
+
+        fn set(&mut self, name: &'static str, value: Option<&str>) {
+            if !self.saved.iter().any(|(saved, _)| *saved == name) {
+                self.saved.push((name, std::env::var_os(name)));
+            }
+            // SAFETY: every test touching the environment holds ENV
+            unsafe {
+                match value {
+                    Some(value) => std::env::set_var(name, value),
+                    None => std::env::remove_var(name),
+                }
+            }
+        }
+    }
+
+    impl Drop for EnvGuard {
+        fn drop(&mut self) {
+            for (name, value) in self.saved.drain(..).rev() {
+                // SAFETY: ENV is still held
+                unsafe {
+                    match value {
+                        Some(value) => std::env::set_var(name, value),
+                        None => std::env::remove_var(name),
+                    }
+                }
+            }
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("stack-credentials-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // Writes a credential helper script printing `output` and returns the
+    // `-c` overrides making it git's only helper
+    fn credential_helper(dir: &std::path::Path, name: &str, output: &str) -> [String; 2] {
+        let script = dir.join(name);
+        fs::write(&script, format!("#!/bin/sh\ncat >/dev/null\nprintf '{}'\n", output)).unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        ["credential.helper=".to_string(), format!("credential.helper={}", script.display())]
+    }
+
+    fn fill_with(config: &[String]) -> impl Fn(&str) -> Result<Option<String>, StackError> + '_ {
+        move |host| {
+            let config: Vec<&str> = config.iter().map(String::as_str).collect();
+            Git::new().credential_fill_with(host, &config)
+        }
+    }
+
+    #[test]
+    fn finds_the_token_of_the_host() {
+        assert_eq!(host_value(HOSTS_YML, "github.com", "oauth_token").as_deref(), Some("file-token"));
+        assert_eq!(host_value(HOSTS_YML, "github.example.com", "oauth_token").as_deref(), Some("other-host-token"));
+        assert_eq!(host_value(HOSTS_YML, "gitlab.com", "oauth_token"), None);
+        assert_eq!(host_value(HOSTS_YML, "github.example.com", "git_protocol"), None);
+
+        let glab = "hosts:\n  'gitlab.example.com':\n    token: glpat-123\n    api_protocol: https\n";
+        assert_eq!(host_value(glab, "gitlab.example.com", "token").as_deref(), Some("glpat-123"));
+        assert_eq!(host_value("gitlab.com:\n  token:\n", "gitlab.com", "token"), None);
+    }
+
+    #[test]
+    fn looks_in_the_environment_then_git_then_cli_config() {
+        let mut env = EnvGuard::new();
+        let dir = temp_dir("order");
+        fs::create_dir_all(dir.join("gh")).unwrap();
+        fs::write(dir.join("gh").join("hosts.yml"), format!("{}:\n    oauth_token: file-token\n", GITHUB_HOST)).unwrap();
+        env.set("GH_CONFIG_DIR", Some(dir.join("gh").to_str().unwrap()));
+        env.set("GITHUB_TOKEN", None);
+        let helper = credential_helper(&dir, "helper.sh", "username=someone\\npassword=helper-token\\n");
+        let token = || find_token(ForgeKind::GitHub, GITHUB_HOST, fill_with(&helper));
+
+        env.set("STACK_FORGE_TOKEN", Some("env-token"));
+        assert_eq!(token().unwrap(), "env-token");
+        env.set("STACK_FORGE_TOKEN", None);
+        env.set("GITHUB_TOKEN", Some("github-env-token"));
+        assert_eq!(token().unwrap(), "github-env-token");
+
+        env.set("GITHUB_TOKEN", None);
+        assert_eq!(token().unwrap(), "helper-token");
+
+        // A helper without a password for the host falls through
+        let empty = credential_helper(&dir, "empty.sh", "");
+        let token = || find_token(ForgeKind::GitHub, GITHUB_HOST, fill_with(&empty));
+        assert_eq!(token().unwrap(), "file-token");
+
+        env.set("GH_CONFIG_DIR", Some(dir.join("missing").to_str().unwrap()));
+        assert!(token().is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn gitlab_tokens_come_from_glab_config() {
+        let mut env = EnvGuard::new();
+        let dir = temp_dir("glab");
+        fs::write(dir.join("config.yml"), format!("hosts:\n    {}:\n        token: glab-token\n", GITLAB_HOST)).unwrap();
+        env.set("GLAB_CONFIG_DIR", Some(dir.to_str().unwrap()));
+        env.set("STACK_FORGE_TOKEN", None);
+        env.set("GITLAB_TOKEN", None);
+        let empty = credential_helper(&dir, "empty.sh", "");
+        let token = || find_token(ForgeKind::GitLab, GITLAB_HOST, fill_with(&empty));
+
+        assert_eq!(token().unwrap(), "glab-token");
+        // GitHub's variable is not used for GitLab
+        env.set("GITHUB_TOKEN", Some("github-env-token"));
+        assert_eq!(token().unwrap(), "glab-token");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,391 @@
+use crate::error::StackError;
+
+/// The repository a remote points at, e.g. `github.com` / `owner/name`.
+pub struct RemoteRepo {
+    pub host: String,
+    pub path: String,
+}
+
+impl RemoteRepo {
+    /// Parses `git@host:owner/name.git`, `ssh://git@host/owner/name` and
+    /// `https://host/owner/name.git` style remote URLs.
+    pub fn from_url(url: &str) -> Result<Self, StackError> {
+        let url = url.trim();
+        let without_scheme = url.split_once("://").map(|(_, rest)| rest);
+        let (host, path) = match without_scheme {
+            Some(rest) => rest.split_once('/').unwrap_or((rest, "")),
+            // scp-like syntax
+            None => url.split_once(':').unwrap_or(("", url)),
+        };
+        // Drop any user (git@) and port
+        let host = host.rsplit('@').next().unwrap_or(host);
+        let host = host.split(':').next().unwrap_or(host);
+        let path = path.trim_matches('/').trim_end_matches(".git");
+
+        if host.is_empty() || !path.contains('/') {
+            return Err(StackError::Invalid(format!(
+                "Cannot determine the forge repository from remote URL {}. Set FORGE_REPOSITORY.",
+                url
+            )));
+        }
+        Ok(Self { host: host.to_string(), path: path.to_string() })
+    }
+
+    /// GitLab hosts are recognised by name; everything else is assumed to
+    /// be GitHub. Set FORGE for self-hosted instances on other names.
+    pub fn forge_kind(&self) -> ForgeKind {
+        if self.host.contains("gitlab") {
+            ForgeKind::GitLab
+        } else {
+            ForgeKind::GitHub
+        }
+    }
+
+    /// The REST API base URL for a GitHub or GitHub Enterprise host.
+    pub fn github_api_url(&self) -> String {
+        if self.host == "github.com" {
+            "https://api.github.com".to_string()
+        } else {
+            format!("https://{}/api/v3", self.host)
+        }
+    }
+
+    /// The REST API base URL for gitlab.com or a self-hosted GitLab.
+    pub fn gitlab_api_url(&self) -> String {
+        format!("https://{}/api/v4", self.host)
+    }
+}
+
+/// The web host behind an API URL such as `https://api.github.com` or
+/// `https://gitlab.example.com/api/v4`.
+pub fn host_from_api_url(api_url: &str) -> String {
+    let without_scheme = api_url.split_once("://").map(|(_, rest)| rest).unwrap_or(api_url);
+    let host = without_scheme.split('/').next().unwrap_or_default();
+    let host = host.split(':').next().unwrap_or(host);
+    host.strip_prefix("api.").unwrap_or(host).to_string()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ForgeKind {
+    GitHub,
+    GitLab,
+}
+
+impl ForgeKind {
+    pub fn parse(value: &str) -> Result<Self, StackError> {
+        match value.to_lowercase().as_str() {
+            "github" => Ok(Self::GitHub),
+            "gitlab" => Ok(Self::GitLab),
+            _ => Err(StackError::Invalid(format!("Unknown forge {}. Use github or gitlab.", value))),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum MergeMethod {
+    Merge,
+    Squash,
+    Rebase,
+}
+
+impl MergeMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Merge => "merge",
+            Self::Squash => "squash",
+            Self::Rebase => "rebase",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self, StackError> {
+        [Self::Merge, Self::Squash, Self::Rebase].into_iter()
+            .find(|method| method.as_str() == value.to_lowercase())
+            .ok_or_else(|| StackError::Invalid(format!("Unknown merge method {}. Use merge, squash or rebase.", value)))
+    }
+}
+
+/// A code review host. GitLab merge requests are handled as pull requests,
+/// numbered by their project-local iid.
+pub trait Forge {
+    fn get_pull_request(&self, number: u64) -> Result<PullRequest, StackError>;
+
+    /// The open pull request from `head`, if there is one.
+    fn find_pull_request(&self, head: &str) -> Result<Option<PullRequest>, StackError>;
+
+    fn create_pull_request(&self, head: &str, base: &str, title: &str, body: &str, draft: bool) -> Result<PullRequest, StackError>;
+
+    fn update_pull_request(&self, number: u64, body: &str) -> Result<PullRequest, StackError>;
+
+    fn update_title(&self, pr: &PullRequest, title: &str) -> Result<(), StackError>;
+
+    /// Requests reviews from users, and from teams given as `org/team`.
+    fn request_reviewers(&self, pr: &PullRequest, reviewers: &[String]) -> Result<(), StackError>;
+
+    fn add_labels(&self, pr: &PullRequest, labels: &[String]) -> Result<(), StackError>;
+
+    /// Changes the branch the pull request merges into.
+    fn retarget_pull_request(&self, number: u64, base: &str) -> Result<PullRequest, StackError>;
+
+    fn get_status(&self, number: u64) -> Result<PullRequestStatus, StackError>;
+
+    /// Only the state of the pull request, without its reviews and checks.
+    fn get_state(&self, number: u64) -> Result<PullRequestState, StackError>;
+
+    /// Asks the forge to merge the pull request. The merge may complete
+    /// asynchronously; poll `get_state` to find out.
+    fn merge_pull_request(&self, number: u64, method: MergeMethod) -> Result<(), StackError>;
+
+    /// The merge methods `merge_pull_request` supports.
+    fn merge_methods(&self) -> &'static [MergeMethod] {
+        &[MergeMethod::Merge, MergeMethod::Squash, MergeMethod::Rebase]
+    }
+
+    /// How a pull request is linked from markdown on this forge.
+    fn reference(&self, number: u64) -> String {
+        format!("#{}", number)
+    }
+}
+
+pub struct PullRequest {
+    pub number: u64,
+    pub url: String,
+    pub head: String,
+    pub base: String,
+    pub title: String,
+    pub body: String,
+    pub draft: bool,
+    // Pending review requests, users by login and teams as `org/team`
+    pub reviewers: Vec<String>,
+    pub labels: Vec<String>,
+}
+
+/// What `stack submit` sets on every pull request, from PR_* settings.
+pub struct PullRequestSettings {
+    pub draft: bool,
+    pub reviewers: Vec<String>,
+    pub labels: Vec<String>,
+    pub title_template: String,
+}
+
+impl PullRequestSettings {
+    /// Expands `{subject}`, `{branch}`, `{stack}`, `{index}` (1-based, from
+    /// the bottom) and `{count}`.
+    pub fn title(&self, subject: &str, branch: &str, stack_name: &str, index: usize, count: usize) -> String {
+        self.title_template
+            .replace("{subject}", subject)
+            .replace("{branch}", branch)
+            .replace("{stack}", stack_name)
+            .replace("{index}", &(index + 1).to_string())
+            .replace("{count}", &count.to_string())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PullRequestState {
+    Open,
+    Draft,
+    Merged,
+    Closed,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReviewDecision {
+    Approved,
+    ChangesRequested,
+    ReviewRequired,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CheckStatus {
+    Success,
+    Pending,
+    Failure,
+    // No checks configured
+    None,
+}
+
+pub struct PullRequestStatus {
+    pub state: PullRequestState,
+    pub review: ReviewDecision,
+    pub checks: CheckStatus,
+}
+
+impl PullRequestState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Draft => "draft",
+            Self::Merged => "merged",
+            Self::Closed => "closed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        [Self::Open, Self::Draft, Self::Merged, Self::Closed].into_iter().find(|s| s.as_str() == value)
+    }
+}
+
+impl ReviewDecision {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Approved => "approved",
+            Self::ChangesRequested => "changes requested",
+            Self::ReviewRequired => "review required",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        [Self::Approved, Self::ChangesRequested, Self::ReviewRequired].into_iter().find(|s| s.as_str() == value)
+    }
+}
+
+impl CheckStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Success => "passing",
+            Self::Pending => "pending",
+            Self::Failure => "failing",
+            Self::None => "no checks",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        [Self::Success, Self::Pending, Self::Failure, Self::None].into_iter().find(|s| s.as_str() == value)
+    }
+
+    /// Combines the results of several checks: any failure fails, then any
+    /// pending check is pending.
+    pub fn combine(statuses: impl IntoIterator<Item = CheckStatus>) -> CheckStatus {
+        statuses.into_iter().fold(CheckStatus::None, |combined, status| match (combined, status) {
+            (Self::Failure, _) | (_, Self::Failure) => Self::Failure,
+            (Self::Pending, _) | (_, Self::Pending) => Self::Pending,
+            (Self::Success, _) | (_, Self::Success) => Self::Success,
+            _ => Self::None,
+        })
+    }
+}
+
+impl PullRequestStatus {
+    /// `state|review|checks`, as stored in the status cache.
+    pub fn to_cache_string(&self) -> String {
+        format!("{}|{}|{}", self.state.as_str(), self.review.as_str(), self.checks.as_str())
+    }
+
+    pub fn from_cache_string(value: &str) -> Option<Self> {
+        let mut fields = value.split('|');
+        Some(Self {
+            state: PullRequestState::parse(fields.next()?)?,
+            review: ReviewDecision::parse(fields.next()?)?,
+            checks: CheckStatus::parse(fields.next()?)?,
+        })
+    }
+}
+
+const SECTION_START: &str = "<!-- stack:start -->";
+const SECTION_END: &str = "<!-- stack:end -->";
+
+/// The navigation section listing every pull request in a stack, top first,
+/// with the one it is rendered for marked.
+pub fn render_stack_section(stack_name: &str, references: &[String], current: usize, base_branch: &str) -> String {
+    let mut section = format!("{}\n**Stack** `{}`\n", SECTION_START, stack_name);
+    for (index, reference) in references.iter().enumerate().rev() {
+        if index == current {
+            section.push_str(&format!("- **{}** 👈\n", reference));
+        } else {
+            section.push_str(&format!("- {}\n", reference));
+        }
+    }
+    section.push_str(&format!("- `{}`\n{}", base_branch, SECTION_END));
+    section
+}
+
+/// Replaces the stack section of a description, or appends one. Text outside
+/// the markers is kept as is.
+pub fn replace_stack_section(body: &str, section: &str) -> String {
+    // Matched from the end marker back, so that a stray start marker earlier
+    // in the text is left alone
+    if let Some(end) = body.find(SECTION_END)
+        && let Some(start) = body[..end].rfind(SECTION_START)
+    {
+        let end = end + SECTION_END.len();
+        return format!("{}{}{}", &body[..start], section, &body[end..]);
+    }
+    let separator = if body.is_empty() || body.ends_with("\n\n") {
+        ""
+    } else if body.ends_with('\n') {
+        "\n"
+    } else {
+        "\n\n"
+    };
+    format!("{}{}{}", body, separator, section)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_round_trips_through_the_cache() {
+        let status = PullRequestStatus {
+            state: PullRequestState::Draft,
+            review: ReviewDecision::ChangesRequested,
+            checks: CheckStatus::None,
+        };
+        let cached = PullRequestStatus::from_cache_string(&status.to_cache_string()).unwrap();
+        assert_eq!(cached.to_cache_string(), "draft|changes requested|no checks");
+        assert_eq!(cached.state, PullRequestState::Draft);
+        assert_eq!(cached.review, ReviewDecision::ChangesRequested);
+        assert_eq!(cached.checks, CheckStatus::None);
+    }
+
+    #[test]
+    fn unreadable_cache_entries_are_ignored() {
+        assert!(PullRequestStatus::from_cache_string("").is_none());
+        assert!(PullRequestStatus::from_cache_string("open|approved").is_none());
+        assert!(PullRequestStatus::from_cache_string("open|approved|green").is_none());
+    }
+
+    #[test]
+    fn failures_win_over_pending_over_success() {
+        use CheckStatus::*;
+        assert_eq!(CheckStatus::combine([]), None);
+        assert_eq!(CheckStatus::combine([Success, Success]), Success);
+        assert_eq!(CheckStatus::combine([Success, Pending]), Pending);
+        assert_eq!(CheckStatus::combine([Pending, Failure, Success]), Failure);
+        assert_eq!(CheckStatus::combine([None, Success]), Success);
+    }
+
+    fn section(name: &str) -> String {
+        render_stack_section(name, &["#1".to_string(), "#2".to_string()], 0, "main")
+    }
+
+    #[test]
+    fn replaces_only_the_section() {
+        let body = format!("Intro  \n\n{}\n\nTrailing notes\n", section("old"));
+        assert_eq!(
+            replace_stack_section(&body, &section("new")),
+            format!("Intro  \n\n{}\n\nTrailing notes\n", section("new"))
+        );
+    }
+
+    #[test]
+    fn appends_a_section_after_the_text() {
+        assert_eq!(replace_stack_section("Fixes a bug", &section("s")), format!("Fixes a bug\n\n{}", section("s")));
+        assert_eq!(replace_stack_section("Fixes a bug\n", &section("s")), format!("Fixes a bug\n\n{}", section("s")));
+        assert_eq!(replace_stack_section("Fixes a bug  \n\n", &section("s")), format!("Fixes a bug  \n\n{}", section("s")));
+    }
+
+    #[test]
+    fn fills_an_empty_description() {
+        assert_eq!(replace_stack_section("", &section("s")), section("s"));
+    }
+
+    #[test]
+    fn keeps_a_start_marker_without_an_end() {
+        let body = format!("Notes mentioning {} here\nmore text", SECTION_START);
+        let first = replace_stack_section(&body, &section("old"));
+        assert_eq!(first, format!("{}\n\n{}", body, section("old")));
+        // Updating again replaces the appended section, not the stray marker
+        assert_eq!(replace_stack_section(&first, &section("new")), format!("{}\n\n{}", body, section("new")));
+    }
+}
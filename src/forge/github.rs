@@ -0,0 +1,341 @@
+use serde_json::{json, Value};
+use crate::error::StackError;
+use std::collections::HashMap;
+use super::forge::{CheckStatus, Forge, MergeMethod, PullRequest, PullRequestState, PullRequestStatus, ReviewDecision};
+use super::http::{encode, request, Response};
+
+pub struct GitHub {
+    api_url: String,
+    token: String,
+    // owner/name
+    repo: String,
+}
+
+fn pull_request_from(value: &Value) -> Result<PullRequest, StackError> {
+    let field = |pointer: &str| value.pointer(pointer).and_then(Value::as_str).unwrap_or_default().to_string();
+    let number = value.get("number").and_then(Value::as_u64)
+        .ok_or_else(|| StackError::Forge("Pull request response has no number".to_string()))?;
+    let names = |key: &str, name: &str| -> Vec<String> {
+        value.get(key).and_then(Value::as_array).into_iter().flatten()
+            .filter_map(|item| item.get(name).and_then(Value::as_str).map(|name| name.to_string()))
+            .collect()
+    };
+    let owner = field("/base/repo/owner/login");
+    let mut reviewers = names("requested_reviewers", "login");
+    reviewers.extend(names("requested_teams", "slug").into_iter().map(|team| format!("{}/{}", owner, team)));
+    Ok(PullRequest {
+        number,
+        url: field("/html_url"),
+        head: field("/head/ref"),
+        base: field("/base/ref"),
+        title: field("/title"),
+        body: field("/body"),
+        draft: value.get("draft").and_then(Value::as_bool).unwrap_or(false),
+        reviewers,
+        labels: names("labels", "name"),
+    })
+}
+
+fn state_from(pr: &Value) -> PullRequestState {
+    if pr.get("merged").and_then(Value::as_bool).unwrap_or(false) {
+        PullRequestState::Merged
+    } else if pr.get("state").and_then(Value::as_str) == Some("closed") {
+        PullRequestState::Closed
+    } else if pr.get("draft").and_then(Value::as_bool).unwrap_or(false) {
+        PullRequestState::Draft
+    } else {
+        PullRequestState::Open
+    }
+}
+
+impl GitHub {
+    pub fn new(api_url: &str, token: String, repo: &str) -> Self {
+        Self {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            token,
+            repo: repo.to_string(),
+        }
+    }
+
+    fn call(&self, method: &str, path: &str, body: Option<&Value>) -> Result<Response, StackError> {
+        let url = format!("{}/repos/{}{}", self.api_url, self.repo, path);
+        let headers = [
+            ("Accept", "application/vnd.github+json".to_string()),
+            ("Authorization", format!("Bearer {}", self.token)),
+            ("X-GitHub-Api-Version", "2022-11-28".to_string()),
+            ("User-Agent", "stack".to_string()),
+        ];
+        let response = request(method, &url, &headers, body)?;
+        if !response.is_success() {
+            return Err(StackError::Forge(format!("{} {}: {}", method, path, response.message())));
+        }
+        Ok(response)
+    }
+}
+
+impl Forge for GitHub {
+    fn get_pull_request(&self, number: u64) -> Result<PullRequest, StackError> {
+        let response = self.call("GET", &format!("/pulls/{}", number), None)?;
+        pull_request_from(&response.body)
+    }
+
+    fn get_status(&self, number: u64) -> Result<PullRequestStatus, StackError> {
+        let pr = self.call("GET", &format!("/pulls/{}", number), None)?.body;
+        let state = state_from(&pr);
+
+        // Only each reviewer's latest approving or blocking review counts
+        let reviews = self.call("GET", &format!("/pulls/{}/reviews?per_page=100", number), None)?.body;
+        let mut latest: HashMap<String, String> = HashMap::new();
+        for review in reviews.as_array().into_iter().flatten() {
+            let user = review.pointer("/user/login").and_then(Value::as_str).unwrap_or_default();
+            let review_state = review.get("state").and_then(Value::as_str).unwrap_or_default();
+            if matches!(review_state, "APPROVED" | "CHANGES_REQUESTED" | "DISMISSED") {
+                latest.insert(user.to_string(), review_state.to_string());
+            }
+        }
+        let review = if latest.values().any(|s| s == "CHANGES_REQUESTED") {
+            ReviewDecision::ChangesRequested
+        } else if latest.values().any(|s| s == "APPROVED") {
+            ReviewDecision::Approved
+        } else {
+            ReviewDecision::ReviewRequired
+        };
+
+        let sha = pr.pointer("/head/sha").and_then(Value::as_str).unwrap_or_default();
+        let mut checks = Vec::new();
+        let combined = self.call("GET", &format!("/commits/{}/status", sha), None)?.body;
+        for status in combined.get("statuses").and_then(Value::as_array).into_iter().flatten() {
+            checks.push(match status.get("state").and_then(Value::as_str) {
+                Some("success") => CheckStatus::Success,
+                Some("pending") => CheckStatus::Pending,
+                _ => CheckStatus::Failure,
+            });
+        }
+        let runs = self.call("GET", &format!("/commits/{}/check-runs?per_page=100", sha), None)?.body;
+        for run in runs.get("check_runs").and_then(Value::as_array).into_iter().flatten() {
+            checks.push(match (run.get("status").and_then(Value::as_str), run.get("conclusion").and_then(Value::as_str)) {
+                (Some("completed"), Some("success" | "neutral" | "skipped")) => CheckStatus::Success,
+                (Some("completed"), _) => CheckStatus::Failure,
+                _ => CheckStatus::Pending,
+            });
+        }
+
+        Ok(PullRequestStatus { state, review, checks: CheckStatus::combine(checks) })
+    }
+
+    fn get_state(&self, number: u64) -> Result<PullRequestState, StackError> {
+        let pr = self.call("GET", &format!("/pulls/{}", number), None)?.body;
+        Ok(state_from(&pr))
+    }
+
+    fn find_pull_request(&self, head: &str) -> Result<Option<PullRequest>, StackError> {
+        let owner = self.repo.split('/').next().unwrap_or_default();
+        let path = format!("/pulls?state=open&head={}", encode(&format!("{}:{}", owner, head)));
+        let response = self.call("GET", &path, None)?;
+        match response.body.as_array().and_then(|prs| prs.first()) {
+            Some(pr) => Ok(Some(pull_request_from(pr)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn create_pull_request(&self, head: &str, base: &str, title: &str, body: &str, draft: bool) -> Result<PullRequest, StackError> {
+        let payload = json!({ "head": head, "base": base, "title": title, "body": body, "draft": draft });
+        let response = self.call("POST", "/pulls", Some(&payload))?;
+        pull_request_from(&response.body)
+    }
+
+    fn update_pull_request(&self, number: u64, body: &str) -> Result<PullRequest, StackError> {
+        let response = self.call("PATCH", &format!("/pulls/{}", number), Some(&json!({ "body": body })))?;
+        pull_request_from(&response.body)
+    }
+
+    fn update_title(&self, pr: &PullRequest, title: &str) -> Result<(), StackError> {
+        self.call("PATCH", &format!("/pulls/{}", pr.number), Some(&json!({ "title": title })))?;
+        Ok(())
+    }
+
+    fn request_reviewers(&self, pr: &PullRequest, reviewers: &[String]) -> Result<(), StackError> {
+        // Requesting a review again from someone who already reviewed would
+        // dismiss their review state, so they are left alone
+        let reviews = self.call("GET", &format!("/pulls/{}/reviews?per_page=100", pr.number), None)?.body;
+        let reviewed: Vec<&str> = reviews.as_array().into_iter().flatten()
+            .filter_map(|review| review.pointer("/user/login").and_then(Value::as_str))
+            .collect();
+        let missing = reviewers.iter()
+            .filter(|reviewer| !pr.reviewers.contains(reviewer) && !reviewed.contains(&reviewer.as_str()));
+        let (teams, users): (Vec<&String>, Vec<&String>) = missing.partition(|reviewer| reviewer.contains('/'));
+        if teams.is_empty() && users.is_empty() {
+            return Ok(());
+        }
+        let team_slugs: Vec<&str> = teams.iter()
+            .filter_map(|team| team.rsplit('/').next())
+            .collect();
+        let payload = json!({ "reviewers": users, "team_reviewers": team_slugs });
+        self.call("POST", &format!("/pulls/{}/requested_reviewers", pr.number), Some(&payload))?;
+        Ok(())
+    }
+
+    fn add_labels(&self, pr: &PullRequest, labels: &[String]) -> Result<(), StackError> {
+        let missing: Vec<&String> = labels.iter().filter(|label| !pr.labels.contains(label)).collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        self.call("POST", &format!("/issues/{}/labels", pr.number), Some(&json!({ "labels": missing })))?;
+        Ok(())
+    }
+
+    fn retarget_pull_request(&self, number: u64, base: &str) -> Result<PullRequest, StackError> {
+        let response = self.call("PATCH", &format!("/pulls/{}", number), Some(&json!({ "base": base })))?;
+        pull_request_from(&response.body)
+    }
+
+    fn merge_pull_request(&self, number: u64, method: MergeMethod) -> Result<(), StackError> {
+        let payload = json!({ "merge_method": method.as_str() });
+        self.call("PUT", &format!("/pulls/{}/merge", number), Some(&payload))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forge::http::mock::MockServer;
+
+    fn status(pr: Value, reviews: Value, statuses: Value, check_runs: Value) -> PullRequestStatus {
+        let server = MockServer::start(vec![
+            ("GET", "/repos/o/r/pulls/1", 200, pr),
+            ("GET", "/repos/o/r/pulls/1/reviews?per_page=100", 200, reviews),
+            ("GET", "/repos/o/r/commits/abc/status", 200, json!({ "statuses": statuses })),
+            ("GET", "/repos/o/r/commits/abc/check-runs?per_page=100", 200, json!({ "check_runs": check_runs })),
+        ]);
+        GitHub::new(&server.url, "token".to_string(), "o/r").get_status(1).unwrap()
+    }
+
+    fn open_pr() -> Value {
+        json!({ "number": 1, "state": "open", "head": { "sha": "abc" } })
+    }
+
+    fn review(user: &str, state: &str) -> Value {
+        json!({ "user": { "login": user }, "state": state })
+    }
+
+    #[test]
+    fn latest_review_per_reviewer_decides() {
+        let reviews = json!([
+            review("alice", "CHANGES_REQUESTED"),
+            review("alice", "COMMENTED"),
+            review("alice", "APPROVED"),
+            review("bob", "APPROVED"),
+        ]);
+        assert_eq!(status(open_pr(), reviews, json!([]), json!([])).review, ReviewDecision::Approved);
+
+        let reviews = json!([review("alice", "APPROVED"), review("bob", "CHANGES_REQUESTED")]);
+        assert_eq!(status(open_pr(), reviews, json!([]), json!([])).review, ReviewDecision::ChangesRequested);
+
+        let reviews = json!([review("alice", "APPROVED"), review("alice", "DISMISSED"), review("bob", "COMMENTED")]);
+        assert_eq!(status(open_pr(), reviews, json!([]), json!([])).review, ReviewDecision::ReviewRequired);
+    }
+
+    #[test]
+    fn statuses_and_check_runs_are_combined() {
+        let passing = json!([{ "state": "success" }]);
+        let running = json!([{ "status": "in_progress", "conclusion": null }]);
+        let skipped = json!([{ "status": "completed", "conclusion": "skipped" }]);
+        let failed = json!([{ "status": "completed", "conclusion": "timed_out" }]);
+
+        assert_eq!(status(open_pr(), json!([]), json!([]), json!([])).checks, CheckStatus::None);
+        assert_eq!(status(open_pr(), json!([]), passing.clone(), skipped).checks, CheckStatus::Success);
+        assert_eq!(status(open_pr(), json!([]), passing.clone(), running).checks, CheckStatus::Pending);
+        assert_eq!(status(open_pr(), json!([]), passing, failed).checks, CheckStatus::Failure);
+        assert_eq!(status(open_pr(), json!([]), json!([{ "state": "error" }]), json!([])).checks, CheckStatus::Failure);
+    }
+
+    #[test]
+    fn every_status_request_sends_the_token() {
+        let server = MockServer::start(vec![
+            ("GET", "/repos/o/r/pulls/1", 200, open_pr()),
+            ("GET", "/repos/o/r/pulls/1/reviews?per_page=100", 200, json!([])),
+            ("GET", "/repos/o/r/commits/abc/status", 200, json!({})),
+            ("GET", "/repos/o/r/commits/abc/check-runs?per_page=100", 200, json!({})),
+        ]);
+        GitHub::new(&server.url, "secret-token".to_string(), "o/r").get_status(1).unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 4);
+        for request in requests {
+            assert_eq!(request.headers["authorization"], "Bearer secret-token");
+            assert_eq!(request.body, Value::Null);
+        }
+    }
+
+    #[test]
+    fn merged_wins_over_closed_and_draft() {
+        let state = |pr: Value| status(pr, json!([]), json!([]), json!([])).state;
+        assert_eq!(state(open_pr()), PullRequestState::Open);
+        assert_eq!(state(json!({ "state": "open", "draft": true, "head": { "sha": "abc" } })), PullRequestState::Draft);
+        assert_eq!(state(json!({ "state": "closed", "head": { "sha": "abc" } })), PullRequestState::Closed);
+        assert_eq!(state(json!({ "state": "closed", "merged": true, "head": { "sha": "abc" } })), PullRequestState::Merged);
+    }
+
+    #[test]
+    fn state_needs_only_the_pull_request() {
+        let server = MockServer::start(vec![
+            ("GET", "/repos/o/r/pulls/1", 200, json!({ "state": "closed", "merged": true })),
+        ]);
+        let github = GitHub::new(&server.url, "token".to_string(), "o/r");
+        assert_eq!(github.get_state(1).unwrap(), PullRequestState::Merged);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    fn pull_request(number: u64, head: &str, base: &str) -> Value {
+        json!({
+            "number": number,
+            "html_url": format!("https://github.com/o/r/pull/{}", number),
+            "head": { "ref": head, "sha": "abc" },
+            "base": { "ref": base, "repo": { "owner": { "login": "o" } } },
+            "title": "Title",
+            "requested_teams": [{ "slug": "core" }],
+        })
+    }
+
+    #[test]
+    fn creates_pull_requests_on_their_parent() {
+        let server = MockServer::start(vec![("POST", "/repos/o/r/pulls", 201, pull_request(7, "b", "a"))]);
+        let github = GitHub::new(&server.url, "token".to_string(), "o/r");
+        let pr = github.create_pull_request("b", "a", "Title", "", true).unwrap();
+        assert_eq!((pr.number, pr.head.as_str(), pr.base.as_str()), (7, "b", "a"));
+        assert_eq!(pr.reviewers, vec!["o/core".to_string()]);
+        assert_eq!(
+            server.requests()[0].body,
+            json!({ "head": "b", "base": "a", "title": "Title", "body": "", "draft": true })
+        );
+    }
+
+    #[test]
+    fn finds_open_pull_requests_by_owner_and_branch() {
+        let server = MockServer::start(vec![
+            ("GET", "/repos/o/r/pulls?state=open&head=o%3Afeature%2Fb", 200, json!([pull_request(3, "feature/b", "main")])),
+            ("GET", "/repos/o/r/pulls?state=open&head=o%3Anone", 200, json!([])),
+        ]);
+        let github = GitHub::new(&server.url, "token".to_string(), "o/r");
+        assert_eq!(github.find_pull_request("feature/b").unwrap().map(|pr| pr.number), Some(3));
+        assert!(github.find_pull_request("none").unwrap().is_none());
+    }
+
+    #[test]
+    fn retargets_by_patching_the_base() {
+        let server = MockServer::start(vec![("PATCH", "/repos/o/r/pulls/3", 200, pull_request(3, "b", "main"))]);
+        let github = GitHub::new(&server.url, "token".to_string(), "o/r");
+        assert_eq!(github.retarget_pull_request(3, "main").unwrap().base, "main");
+        assert_eq!(server.requests()[0].body, json!({ "base": "main" }));
+    }
+
+    #[test]
+    fn api_errors_carry_the_message() {
+        let server = MockServer::start(vec![
+            ("POST", "/repos/o/r/pulls", 422, json!({ "message": "A pull request already exists for o:b." })),
+        ]);
+        let github = GitHub::new(&server.url, "token".to_string(), "o/r");
+        let err = github.create_pull_request("b", "a", "Title", "", false).err().unwrap();
+        assert!(err.to_string().contains("A pull request already exists for o:b."));
+    }
+}
@@ -0,0 +1,306 @@
+use serde_json::{json, Value};
+use crate::error::StackError;
+use super::forge::{CheckStatus, Forge, MergeMethod, PullRequest, PullRequestState, PullRequestStatus, ReviewDecision};
+use super::http::{encode, request, Response};
+
+const DRAFT_PREFIX: &str = "Draft: ";
+
+pub struct GitLab {
+    api_url: String,
+    token: String,
+    // group/name, possibly with subgroups
+    project: String,
+}
+
+fn merge_request_from(value: &Value) -> Result<PullRequest, StackError> {
+    let field = |pointer: &str| value.pointer(pointer).and_then(Value::as_str).unwrap_or_default().to_string();
+    let number = value.get("iid").and_then(Value::as_u64)
+        .ok_or_else(|| StackError::Forge("Merge request response has no iid".to_string()))?;
+    let draft = value.get("draft").and_then(Value::as_bool).unwrap_or(false);
+    let title = field("/title");
+    Ok(PullRequest {
+        number,
+        url: field("/web_url"),
+        head: field("/source_branch"),
+        base: field("/target_branch"),
+        // Drafts are marked by a title prefix on GitLab
+        title: title.strip_prefix(DRAFT_PREFIX).map(str::to_string).unwrap_or(title),
+        body: field("/description"),
+        draft,
+        reviewers: value.get("reviewers").and_then(Value::as_array).into_iter().flatten()
+            .filter_map(|user| user.get("username").and_then(Value::as_str).map(|name| name.to_string()))
+            .collect(),
+        labels: value.get("labels").and_then(Value::as_array).into_iter().flatten()
+            .filter_map(|label| label.as_str().map(|label| label.to_string()))
+            .collect(),
+    })
+}
+
+fn state_from(mr: &Value) -> PullRequestState {
+    match mr.get("state").and_then(Value::as_str) {
+        Some("merged") => PullRequestState::Merged,
+        Some("closed") | Some("locked") => PullRequestState::Closed,
+        _ if mr.get("draft").and_then(Value::as_bool).unwrap_or(false) => PullRequestState::Draft,
+        _ => PullRequestState::Open,
+    }
+}
+
+impl GitLab {
+    pub fn new(api_url: &str, token: String, project: &str) -> Self {
+        Self {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            token,
+            project: project.to_string(),
+        }
+    }
+
+    fn call(&self, method: &str, path: &str, body: Option<&Value>) -> Result<Response, StackError> {
+        self.call_api(method, &format!("/projects/{}{}", encode(&self.project), path), body)
+    }
+
+    fn call_api(&self, method: &str, path: &str, body: Option<&Value>) -> Result<Response, StackError> {
+        let url = format!("{}{}", self.api_url, path);
+        let headers = [
+            ("PRIVATE-TOKEN", self.token.clone()),
+            ("User-Agent", "stack".to_string()),
+        ];
+        let response = request(method, &url, &headers, body)?;
+        if !response.is_success() {
+            return Err(StackError::Forge(format!("{} {}: {}", method, path, response.message())));
+        }
+        Ok(response)
+    }
+}
+
+impl Forge for GitLab {
+    fn get_pull_request(&self, number: u64) -> Result<PullRequest, StackError> {
+        let response = self.call("GET", &format!("/merge_requests/{}", number), None)?;
+        merge_request_from(&response.body)
+    }
+
+    fn find_pull_request(&self, head: &str) -> Result<Option<PullRequest>, StackError> {
+        let path = format!("/merge_requests?state=opened&source_branch={}", encode(head));
+        let response = self.call("GET", &path, None)?;
+        match response.body.as_array().and_then(|mrs| mrs.first()) {
+            Some(mr) => Ok(Some(merge_request_from(mr)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn create_pull_request(&self, head: &str, base: &str, title: &str, body: &str, draft: bool) -> Result<PullRequest, StackError> {
+        let title = if draft { format!("{}{}", DRAFT_PREFIX, title) } else { title.to_string() };
+        let payload = json!({
+            "source_branch": head,
+            "target_branch": base,
+            "title": title,
+            "description": body,
+        });
+        let response = self.call("POST", "/merge_requests", Some(&payload))?;
+        merge_request_from(&response.body)
+    }
+
+    fn update_pull_request(&self, number: u64, body: &str) -> Result<PullRequest, StackError> {
+        let payload = json!({ "description": body });
+        let response = self.call("PUT", &format!("/merge_requests/{}", number), Some(&payload))?;
+        merge_request_from(&response.body)
+    }
+
+    fn update_title(&self, pr: &PullRequest, title: &str) -> Result<(), StackError> {
+        let title = if pr.draft { format!("{}{}", DRAFT_PREFIX, title) } else { title.to_string() };
+        self.call("PUT", &format!("/merge_requests/{}", pr.number), Some(&json!({ "title": title })))?;
+        Ok(())
+    }
+
+    fn request_reviewers(&self, pr: &PullRequest, reviewers: &[String]) -> Result<(), StackError> {
+        let missing: Vec<&String> = reviewers.iter().filter(|reviewer| !pr.reviewers.contains(reviewer)).collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        if let Some(team) = missing.iter().find(|reviewer| reviewer.contains('/')) {
+            return Err(StackError::Invalid(format!("GitLab cannot request reviews from a group ({})", team)));
+        }
+        // Reviewers are set by id, replacing the current ones
+        let mr = self.call("GET", &format!("/merge_requests/{}", pr.number), None)?.body;
+        let mut ids: Vec<u64> = mr.get("reviewers").and_then(Value::as_array).into_iter().flatten()
+            .filter_map(|user| user.get("id").and_then(Value::as_u64))
+            .collect();
+        for username in missing {
+            let users = self.call_api("GET", &format!("/users?username={}", encode(username)), None)?.body;
+            let id = users.as_array().and_then(|users| users.first()).and_then(|user| user.get("id")).and_then(Value::as_u64)
+                .ok_or_else(|| StackError::Forge(format!("No GitLab user named {}", username)))?;
+            ids.push(id);
+        }
+        self.call("PUT", &format!("/merge_requests/{}", pr.number), Some(&json!({ "reviewer_ids": ids })))?;
+        Ok(())
+    }
+
+    fn add_labels(&self, pr: &PullRequest, labels: &[String]) -> Result<(), StackError> {
+        let missing: Vec<&str> = labels.iter().filter(|label| !pr.labels.contains(label)).map(String::as_str).collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        let payload = json!({ "add_labels": missing.join(",") });
+        self.call("PUT", &format!("/merge_requests/{}", pr.number), Some(&payload))?;
+        Ok(())
+    }
+
+    fn retarget_pull_request(&self, number: u64, base: &str) -> Result<PullRequest, StackError> {
+        let payload = json!({ "target_branch": base });
+        let response = self.call("PUT", &format!("/merge_requests/{}", number), Some(&payload))?;
+        merge_request_from(&response.body)
+    }
+
+    fn get_status(&self, number: u64) -> Result<PullRequestStatus, StackError> {
+        let mr = self.call("GET", &format!("/merge_requests/{}", number), None)?.body;
+        let state = state_from(&mr);
+
+        let approvals = self.call("GET", &format!("/merge_requests/{}/approvals", number), None)?.body;
+        let approved_by = approvals.get("approved_by").and_then(Value::as_array).map(Vec::len).unwrap_or(0);
+        let review = if mr.get("detailed_merge_status").and_then(Value::as_str) == Some("requested_changes") {
+            ReviewDecision::ChangesRequested
+        } else if approvals.get("approved").and_then(Value::as_bool).unwrap_or(false) && approved_by > 0 {
+            ReviewDecision::Approved
+        } else {
+            ReviewDecision::ReviewRequired
+        };
+
+        // The head pipeline already aggregates every job
+        let checks = match mr.pointer("/head_pipeline/status").and_then(Value::as_str) {
+            None => CheckStatus::None,
+            Some("success") | Some("skipped") => CheckStatus::Success,
+            Some("failed") | Some("canceled") => CheckStatus::Failure,
+            Some(_) => CheckStatus::Pending,
+        };
+
+        Ok(PullRequestStatus { state, review, checks })
+    }
+
+    fn get_state(&self, number: u64) -> Result<PullRequestState, StackError> {
+        let mr = self.call("GET", &format!("/merge_requests/{}", number), None)?.body;
+        Ok(state_from(&mr))
+    }
+
+    fn merge_pull_request(&self, number: u64, method: MergeMethod) -> Result<(), StackError> {
+        // Merge commits and fast-forwards follow the project's merge method;
+        // only squashing can be chosen per merge request
+        if !self.merge_methods().contains(&method) {
+            return Err(StackError::Invalid(
+                "GitLab cannot rebase while merging; use merge (which follows the project's merge method) or squash".to_string()
+            ));
+        }
+        let payload = json!({ "squash": method == MergeMethod::Squash });
+        self.call("PUT", &format!("/merge_requests/{}/merge", number), Some(&payload))?;
+        Ok(())
+    }
+
+    fn merge_methods(&self) -> &'static [MergeMethod] {
+        &[MergeMethod::Merge, MergeMethod::Squash]
+    }
+
+    fn reference(&self, number: u64) -> String {
+        format!("!{}", number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forge::http::mock::MockServer;
+
+    fn merge_request(iid: u64, source: &str, target: &str, title: &str) -> Value {
+        json!({
+            "iid": iid,
+            "web_url": format!("https://gitlab.com/group/sub/name/-/merge_requests/{}", iid),
+            "source_branch": source,
+            "target_branch": target,
+            "title": title,
+            "draft": title.starts_with(DRAFT_PREFIX),
+            "labels": ["stacked"],
+        })
+    }
+
+    fn gitlab(server: &MockServer) -> GitLab {
+        GitLab::new(&server.url, "secret-token".to_string(), "group/sub/name")
+    }
+
+    #[test]
+    fn creates_drafts_with_a_title_prefix() {
+        let server = MockServer::start(vec![
+            ("POST", "/projects/group%2Fsub%2Fname/merge_requests", 201, merge_request(4, "b", "a", "Draft: Title")),
+        ]);
+        let mr = gitlab(&server).create_pull_request("b", "a", "Title", "", true).unwrap();
+        assert_eq!((mr.number, mr.title.as_str(), mr.draft), (4, "Title", true));
+        assert_eq!(mr.labels, vec!["stacked".to_string()]);
+
+        let request = &server.requests()[0];
+        assert_eq!(request.headers["private-token"], "secret-token");
+        assert_eq!(
+            request.body,
+            json!({ "source_branch": "b", "target_branch": "a", "title": "Draft: Title", "description": "" })
+        );
+    }
+
+    #[test]
+    fn retargets_by_setting_the_target_branch() {
+        let server = MockServer::start(vec![
+            ("PUT", "/projects/group%2Fsub%2Fname/merge_requests/4", 200, merge_request(4, "b", "main", "Title")),
+        ]);
+        assert_eq!(gitlab(&server).retarget_pull_request(4, "main").unwrap().base, "main");
+        assert_eq!(server.requests()[0].body, json!({ "target_branch": "main" }));
+    }
+
+    #[test]
+    fn finds_open_merge_requests_by_source_branch() {
+        let server = MockServer::start(vec![
+            ("GET", "/projects/group%2Fsub%2Fname/merge_requests?state=opened&source_branch=feature%2Fb", 200, json!([merge_request(9, "feature/b", "a", "Title")])),
+        ]);
+        assert_eq!(gitlab(&server).find_pull_request("feature/b").unwrap().map(|mr| mr.number), Some(9));
+    }
+
+    fn status(mr: Value, approvals: Value) -> PullRequestStatus {
+        let server = MockServer::start(vec![
+            ("GET", "/projects/group%2Fsub%2Fname/merge_requests/4", 200, mr),
+            ("GET", "/projects/group%2Fsub%2Fname/merge_requests/4/approvals", 200, approvals),
+        ]);
+        gitlab(&server).get_status(4).unwrap()
+    }
+
+    #[test]
+    fn status_comes_from_approvals_and_the_head_pipeline() {
+        let approved = json!({ "approved": true, "approved_by": [{ "user": { "username": "alice" } }] });
+        let pending = json!({ "approved": false, "approved_by": [] });
+
+        let open = status(json!({ "state": "opened", "head_pipeline": { "status": "running" } }), approved.clone());
+        assert_eq!((open.state, open.review, open.checks), (PullRequestState::Open, ReviewDecision::Approved, CheckStatus::Pending));
+
+        let draft = status(json!({ "state": "opened", "draft": true, "head_pipeline": { "status": "failed" } }), pending.clone());
+        assert_eq!((draft.state, draft.review, draft.checks), (PullRequestState::Draft, ReviewDecision::ReviewRequired, CheckStatus::Failure));
+
+        let blocked = status(json!({ "state": "opened", "detailed_merge_status": "requested_changes" }), approved.clone());
+        assert_eq!((blocked.review, blocked.checks), (ReviewDecision::ChangesRequested, CheckStatus::None));
+
+        // Projects approving without any approver still need a review
+        let rules_only = status(json!({ "state": "merged", "head_pipeline": { "status": "success" } }), json!({ "approved": true, "approved_by": [] }));
+        assert_eq!((rules_only.state, rules_only.review, rules_only.checks), (PullRequestState::Merged, ReviewDecision::ReviewRequired, CheckStatus::Success));
+    }
+
+    #[test]
+    fn state_needs_only_the_merge_request() {
+        let server = MockServer::start(vec![
+            ("GET", "/projects/group%2Fsub%2Fname/merge_requests/4", 200, json!({ "state": "locked" })),
+        ]);
+        assert_eq!(gitlab(&server).get_state(4).unwrap(), PullRequestState::Closed);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn merges_squash_per_request_and_rejects_rebase() {
+        let server = MockServer::start(vec![
+            ("PUT", "/projects/group%2Fsub%2Fname/merge_requests/4/merge", 200, json!({})),
+        ]);
+        gitlab(&server).merge_pull_request(4, MergeMethod::Squash).unwrap();
+        assert_eq!(server.requests()[0].body, json!({ "squash": true }));
+        assert!(gitlab(&server).merge_pull_request(4, MergeMethod::Rebase).is_err());
+        assert_eq!(server.requests().len(), 1);
+    }
+}
@@ -0,0 +1,172 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use serde_json::Value;
+use crate::error::StackError;
+
+// Forge APIs are called through `curl`, the same way git is driven through
+// its CLI. Headers and the request body go through a curl config on stdin so
+// the token never shows up in the process list.
+
+pub struct Response {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl Response {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// The API's own error message, if it sent one.
+    pub fn message(&self) -> String {
+        // GitLab reports validation errors as objects or under `error`
+        match self.body.get("message").or_else(|| self.body.get("error")) {
+            Some(Value::String(message)) => message.clone(),
+            Some(message) => message.to_string(),
+            None => format!("HTTP {}", self.status),
+        }
+    }
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+pub fn request(method: &str, url: &str, headers: &[(&str, String)], body: Option<&Value>) -> Result<Response, StackError> {
+    let mut config = String::new();
+    config.push_str(&format!("url = {}\n", quote(url)));
+    config.push_str(&format!("request = {}\n", quote(method)));
+    for (name, value) in headers {
+        config.push_str(&format!("header = {}\n", quote(&format!("{}: {}", name, value))));
+    }
+    if let Some(body) = body {
+        config.push_str(&format!("header = {}\n", quote("Content-Type: application/json")));
+        config.push_str(&format!("data-binary = {}\n", quote(&body.to_string())));
+    }
+
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--location", "--config", "-", "--write-out", "\n%{http_code}"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| StackError::Forge(format!("Failed to execute curl: {}", e)))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(config.as_bytes())?;
+    }
+    let output = child.wait_with_output()
+        .map_err(|e| StackError::Forge(format!("Failed to wait for curl: {}", e)))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(StackError::Forge(format!("{} {} failed: {}", method, url, stderr.trim())));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (body, status) = stdout.rsplit_once('\n').unwrap_or(("", &stdout));
+    let status = status.trim().parse::<u16>()
+        .map_err(|_| StackError::Forge(format!("{} {} returned no status", method, url)))?;
+    let body = if body.trim().is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_str(body)
+            .map_err(|e| StackError::Forge(format!("{} {} returned invalid JSON: {}", method, url, e)))?
+    };
+    Ok(Response { status, body })
+}
+
+/// Percent-encodes `value` for use in a URL path segment or query.
+pub fn encode(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// A local stand-in for a forge API, for tests.
+#[cfg(test)]
+pub mod mock {
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use serde_json::Value;
+
+    #[derive(Clone, Debug)]
+    pub struct Request {
+        pub method: String,
+        // Including the query
+        pub path: String,
+        // Names lowercased
+        pub headers: HashMap<String, String>,
+        pub body: Value,
+    }
+
+    // (method, path with query, status, body)
+    pub type Route = (&'static str, &'static str, u16, Value);
+
+    /// Answers each request with the first route matching its method and path,
+    /// and a 404 otherwise. Serves until the test process exits.
+    pub struct MockServer {
+        pub url: String,
+        requests: Arc<Mutex<Vec<Request>>>,
+    }
+
+    fn read_request(stream: &TcpStream) -> Option<Request> {
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let mut parts = line.split_whitespace();
+        let (method, path) = (parts.next()?.to_string(), parts.next()?.to_string());
+        let mut headers = HashMap::new();
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).ok()?;
+            let Some((name, value)) = header.trim_end().split_once(':') else {
+                break;
+            };
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+        let length = headers.get("content-length").and_then(|length| length.parse().ok()).unwrap_or(0);
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).ok()?;
+        let body = serde_json::from_slice(&body).unwrap_or(Value::Null);
+        Some(Request { method, path, headers, body })
+    }
+
+    impl MockServer {
+        pub fn start(routes: Vec<Route>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let seen = Arc::clone(&requests);
+            thread::spawn(move || {
+                for mut stream in listener.incoming().flatten() {
+                    let Some(request) = read_request(&stream) else {
+                        continue;
+                    };
+                    let (status, body) = routes.iter()
+                        .find(|(method, path, _, _)| *method == request.method && *path == request.path)
+                        .map(|(_, _, status, body)| (*status, body.to_string()))
+                        .unwrap_or((404, r#"{"message":"Not Found"}"#.to_string()));
+                    seen.lock().unwrap().push(request);
+                    let _ = write!(
+                        stream,
+                        "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status, body.len(), body
+                    );
+                }
+            });
+            Self { url, requests }
+        }
+
+        pub fn requests(&self) -> Vec<Request> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+}
@@ -0,0 +1,5 @@
+pub mod forge;
+pub mod github;
+pub mod gitlab;
+pub mod http;
+pub mod credentials;
\ No newline at end of file
@@ -0,0 +1,252 @@
+// Minimal parsing of `git diff -U0` output, enough to pick individual hunks
+// out of a patch and re-apply them with `git apply --unidiff-zero`.
+
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_count: usize,
+    pub new_start: usize,
+    pub new_count: usize,
+    lines: Vec<String>,
+}
+
+pub struct FilePatch {
+    pub path: String,
+    header: Vec<String>,
+    pub hunks: Vec<Hunk>,
+    // New, deleted, renamed and binary files are not split into hunks
+    pub whole_file: bool,
+}
+
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    match range.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+fn parse_hunk_header(line: &str) -> Option<Hunk> {
+    // @@ -old_start[,old_count] +new_start[,new_count] @@
+    let mut parts = line.split_whitespace().skip(1);
+    let (old_start, old_count) = parse_range(parts.next()?.strip_prefix('-')?)?;
+    let (new_start, new_count) = parse_range(parts.next()?.strip_prefix('+')?)?;
+    Some(Hunk { old_start, old_count, new_start, new_count, lines: Vec::new() })
+}
+
+pub fn parse_patch(patch: &str) -> Vec<FilePatch> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    for line in patch.lines() {
+        if line.starts_with("diff --git ") {
+            let path = line.rsplit_once(" b/").map(|(_, path)| path.to_string()).unwrap_or_default();
+            files.push(FilePatch { path, header: vec![line.to_string()], hunks: Vec::new(), whole_file: false });
+            continue;
+        }
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+        if line.starts_with("@@ ") {
+            match parse_hunk_header(line) {
+                Some(hunk) => file.hunks.push(hunk),
+                None => file.whole_file = true,
+            }
+        } else if let Some(hunk) = file.hunks.last_mut() {
+            hunk.lines.push(line.to_string());
+        } else {
+            if line.starts_with("new file mode")
+                || line.starts_with("deleted file mode")
+                || line.starts_with("rename from")
+                || line.starts_with("Binary files")
+            {
+                file.whole_file = true;
+            }
+            file.header.push(line.to_string());
+        }
+    }
+    files
+}
+
+// The lines a change replaces, as a half-open range. An insertion replaces
+// nothing and sits right after line `start`.
+fn region(start: usize, count: usize) -> (usize, usize) {
+    if count > 0 {
+        (start, start + count)
+    } else {
+        (start + 1, start + 1)
+    }
+}
+
+/// Moves a change to lines `start..start + count` of a commit's result below
+/// that commit, given the commit's own patch for the file. Returns where the
+/// change starts in the commit's parent, or `None` if it overlaps or borders
+/// a change of the commit and would conflict when squashed further down.
+pub fn commute(start: usize, count: usize, commit: &FilePatch) -> Option<usize> {
+    if commit.whole_file {
+        return None;
+    }
+    let (first, end) = region(start, count);
+    let mut shift: isize = 0;
+    for hunk in &commit.hunks {
+        let (hunk_first, hunk_end) = region(hunk.new_start, hunk.new_count);
+        if hunk_first <= end && first <= hunk_end {
+            return None;
+        }
+        if hunk_end < first {
+            shift += hunk.new_count as isize - hunk.old_count as isize;
+        }
+    }
+    Some((start as isize - shift) as usize)
+}
+
+impl FilePatch {
+    fn delta(&self, index: usize) -> isize {
+        self.hunks[index].new_count as isize - self.hunks[index].old_count as isize
+    }
+
+    /// Builds a patch containing only the hunks at `indices`, for a file that
+    /// already has the hunks at `applied` applied. Both sides are renumbered so
+    /// the hunks land in the right place without the ones left out.
+    pub fn select(&self, indices: &[usize], applied: &[usize]) -> String {
+        let mut patch = self.header.join("\n");
+        patch.push('\n');
+        let mut offset: isize = 0;
+        for &index in indices {
+            let hunk = &self.hunks[index];
+            let old_start = hunk.old_start as isize + applied.iter()
+                .filter(|&&other| self.hunks[other].old_start < hunk.old_start)
+                .map(|&other| self.delta(other))
+                .sum::<isize>();
+            // With zero context an empty side names the line before the change
+            let new_start = old_start + offset
+                + match (hunk.old_count, hunk.new_count) {
+                    (0, n) if n > 0 => 1,
+                    (o, 0) if o > 0 => -1,
+                    _ => 0,
+                };
+            patch.push_str(&format!(
+                "@@ -{},{} +{},{} @@\n",
+                old_start.max(0), hunk.old_count, new_start.max(0), hunk.new_count
+            ));
+            for line in &hunk.lines {
+                patch.push_str(line);
+                patch.push('\n');
+            }
+            offset += self.delta(index);
+        }
+        patch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    const ORIGINAL: &str = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+    // An insertion after 2, a deletion of 5 and a change to 8
+    const MODIFIED: &str = "1\n2\nnew a\nnew b\n3\n4\n6\n7\neight\n9\n10\n";
+
+    fn git(dir: &Path, args: &[&str], input: Option<&str>) -> String {
+        let mut command = Command::new("git");
+        command.current_dir(dir).args(args);
+        let output = match input {
+            Some(input) => {
+                let patch = dir.join("selected.patch");
+                fs::write(&patch, input).unwrap();
+                command.arg(&patch).output().unwrap()
+            }
+            None => command.output().unwrap(),
+        };
+        assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    // A repository with ORIGINAL staged and MODIFIED in the work tree
+    fn repository(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("stack-diff-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        git(&dir, &["init", "--quiet"], None);
+        fs::write(dir.join("f.txt"), ORIGINAL).unwrap();
+        git(&dir, &["add", "f.txt"], None);
+        fs::write(dir.join("f.txt"), MODIFIED).unwrap();
+        dir
+    }
+
+    fn apply_in_two_steps(name: &str, first: &[usize], second: &[usize]) {
+        let dir = repository(name);
+        let files = parse_patch(&git(&dir, &["diff", "-U0"], None));
+        assert_eq!(files.len(), 1);
+        let file = &files[0];
+        assert_eq!(file.hunks.len(), 3);
+
+        git(&dir, &["checkout", "--", "f.txt"], None);
+        git(&dir, &["apply", "--unidiff-zero"], Some(&file.select(first, &[])));
+        git(&dir, &["apply", "--unidiff-zero"], Some(&file.select(second, first)));
+        assert_eq!(fs::read_to_string(dir.join("f.txt")).unwrap(), MODIFIED);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn parses_zero_context_hunks() {
+        let dir = repository("parse");
+        let files = parse_patch(&git(&dir, &["diff", "-U0"], None));
+        let counts: Vec<(usize, usize, usize)> = files[0].hunks.iter()
+            .map(|hunk| (hunk.old_start, hunk.old_count, hunk.new_count))
+            .collect();
+        assert_eq!(files[0].path, "f.txt");
+        assert!(!files[0].whole_file);
+        assert_eq!(counts, vec![(2, 0, 2), (5, 1, 0), (8, 1, 1)]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn renumbers_hunks_split_across_two_targets() {
+        apply_in_two_steps("outer-first", &[0, 2], &[1]);
+        apply_in_two_steps("middle-first", &[1], &[0, 2]);
+        apply_in_two_steps("last-first", &[2], &[0, 1]);
+    }
+
+    #[test]
+    fn selects_renumbered_headers() {
+        let dir = repository("headers");
+        let files = parse_patch(&git(&dir, &["diff", "-U0"], None));
+        let headers = |patch: String| -> Vec<String> {
+            patch.lines().filter(|line| line.starts_with("@@ ")).map(str::to_string).collect()
+        };
+        // Without the insertion, the deletion and change keep their lines
+        assert_eq!(headers(files[0].select(&[1, 2], &[])), vec!["@@ -5,1 +4,0 @@", "@@ -8,1 +7,1 @@"]);
+        // After the insertion was applied elsewhere, both sides shift by two
+        assert_eq!(headers(files[0].select(&[1, 2], &[0])), vec!["@@ -7,1 +6,0 @@", "@@ -10,1 +9,1 @@"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn commutes_only_past_unrelated_changes() {
+        let dir = repository("commute");
+        // Commit ORIGINAL, then take MODIFIED as a later commit's changes
+        git(&dir, &["-c", "user.name=stack", "-c", "user.email=stack@example.com", "commit", "--quiet", "-m", "original"], None);
+        git(&dir, &["add", "f.txt"], None);
+        let files = parse_patch(&git(&dir, &["diff", "--cached", "-U0"], None));
+        let commit = &files[0];
+        // Line 1 sits above the insertion and does not move
+        assert_eq!(commute(1, 1, commit), Some(1));
+        // Line 11 ("10") is past all three changes, which add one line overall
+        assert_eq!(commute(11, 1, commit), Some(10));
+        assert_eq!(commute(11, 0, commit), Some(10));
+        // A fix on a changed line or right next to one would conflict
+        assert_eq!(commute(9, 1, commit), None);
+        assert_eq!(commute(10, 1, commit), None);
+        assert_eq!(commute(6, 1, commit), None);
+        assert_eq!(commute(2, 0, commit), None);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn new_files_are_kept_whole() {
+        let patch = "diff --git a/n.txt b/n.txt\nnew file mode 100644\nindex 0000000..d00491f\n--- /dev/null\n+++ b/n.txt\n@@ -0,0 +1 @@\n+1\n";
+        let files = parse_patch(patch);
+        assert_eq!(files[0].path, "n.txt");
+        assert!(files[0].whole_file);
+    }
+}
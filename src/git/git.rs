@@ -1,11 +1,52 @@
 use crate::error::StackError;
 use std::process::{Command, Stdio};
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Write};
+use std::fs;
+use std::path::PathBuf;
 use crate::output::{error, warning};
 
+// Set on every git process spawned by stack, so hooks can tell a restack
+// driven by stack apart from one run by hand.
+pub const INTERNAL_ENV: &str = "STACK_INTERNAL";
+
+fn command_output(cmd: &str, args: &[&str]) -> Result<String, StackError> {
+    command_output_with(cmd, args, &[], None)
+}
+
+fn command_output_with(cmd: &str, args: &[&str], envs: &[(&str, &str)], input: Option<&str>) -> Result<String, StackError> {
+    let mut child = Command::new(cmd)
+        .args(args)
+        .env(INTERNAL_ENV, "1")
+        .envs(envs.iter().copied())
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| StackError::Git(format!("Failed to execute git command: {}", e)))?;
+
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes())?;
+    }
+    let result = child.wait_with_output()
+        .map_err(|e| StackError::Git(format!("Failed to wait for git command: {}", e)))?;
+
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        let message = if stderr.trim().is_empty() {
+            "Git command failed".to_string()
+        } else {
+            stderr.trim().to_string()
+        };
+        return Err(StackError::Git(message));
+    }
+
+    Ok(String::from_utf8_lossy(&result.stdout).trim_end().to_string())
+}
+
 fn run_command(cmd: &str, args: &[&str]) -> Result<(), StackError> {
     let mut child = Command::new(cmd)
         .args(args)
+        .env(INTERNAL_ENV, "1")
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
@@ -49,6 +90,50 @@ fn run_command(cmd: &str, args: &[&str]) -> Result<(), StackError> {
     Ok(())
 }
 
+pub struct RebaseOptions {
+    pub gpg_sign: bool,
+    pub no_verify: bool,
+    pub committer_date_is_author_date: bool,
+    pub strategy_option: Option<String>,
+    pub rebase_merges: bool,
+}
+
+impl RebaseOptions {
+    fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.gpg_sign {
+            args.push("--gpg-sign".to_string());
+        }
+        if self.no_verify {
+            args.push("--no-verify".to_string());
+        }
+        if self.committer_date_is_author_date {
+            args.push("--committer-date-is-author-date".to_string());
+        }
+        if let Some(strategy_option) = &self.strategy_option {
+            args.push("-X".to_string());
+            args.push(strategy_option.clone());
+        }
+        if self.rebase_merges {
+            args.push("--rebase-merges".to_string());
+        }
+        args
+    }
+}
+
+pub struct CommitInfo {
+    pub sha: String,
+    pub subject: String,
+    pub author: String,
+    pub age: String,
+}
+
+pub struct DiffStat {
+    pub files: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
 pub struct Git {}
 
 impl Git {
@@ -70,6 +155,321 @@ impl Git {
         Ok(!output.trim().is_empty()) // empty output means branch doesn't exist
     }
 
+    pub fn current_branch(&self) -> Result<Option<String>, StackError> {
+        // symbolic-ref fails on a detached HEAD, which has no current branch
+        match command_output("git", &["symbolic-ref", "--quiet", "--short", "HEAD"]) {
+            Ok(branch) if !branch.is_empty() => Ok(Some(branch)),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn hooks_dir(&self) -> Result<PathBuf, StackError> {
+        let path = command_output("git", &["rev-parse", "--git-path", "hooks"])?;
+        Ok(PathBuf::from(path))
+    }
+
+    pub fn toplevel(&self) -> Result<PathBuf, StackError> {
+        command_output("git", &["rev-parse", "--show-toplevel"])
+            .map(PathBuf::from)
+            .map_err(|_| StackError::Invalid("Not inside a git work tree. Run `git init` first.".to_string()))
+    }
+
+    /// The branch `refs/remotes/<remote>/HEAD` points to, else a local main or master.
+    pub fn default_branch(&self, remote: &str) -> Result<Option<String>, StackError> {
+        let remote_head = format!("refs/remotes/{}/HEAD", remote);
+        if let Ok(target) = command_output("git", &["symbolic-ref", "--quiet", &remote_head])
+            && let Some(branch) = target.strip_prefix(&format!("refs/remotes/{}/", remote))
+        {
+            return Ok(Some(branch.to_string()));
+        }
+        for branch in ["main", "master"] {
+            if self.check_branch_exists(branch)? {
+                return Ok(Some(branch.to_string()));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn create_branch(&self, branch_name: &str, start_point: &str) -> Result<(), StackError> {
+        if self.check_branch_exists(branch_name)? {
+            return Err(StackError::Invalid(format!("Branch {} already exists", branch_name)));
+        }
+        run_command("git", &["checkout", "-b", branch_name, start_point])
+    }
+
+    pub fn has_staged_changes(&self) -> Result<bool, StackError> {
+        let staged = command_output("git", &["diff", "--cached", "--name-only"])?;
+        Ok(!staged.trim().is_empty())
+    }
+
+    pub fn commit(&self, message: &str) -> Result<(), StackError> {
+        run_command("git", &["commit", "--message", message])
+    }
+
+    /// Commits reachable from `branch` but not `base`, oldest first, as (short sha, subject).
+    pub fn commits(&self, base: &str, branch: &str) -> Result<Vec<(String, String)>, StackError> {
+        let range = format!("{}..{}", base, branch);
+        let log = command_output("git", &["log", "--reverse", "--format=%h %s", &range])?;
+        Ok(log.lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                let (sha, subject) = line.split_once(' ').unwrap_or((line, ""));
+                (sha.to_string(), subject.to_string())
+            })
+            .collect())
+    }
+
+    pub fn rev_parse(&self, reference: &str) -> Result<String, StackError> {
+        command_output("git", &["rev-parse", "--verify", "--quiet", reference])
+            .map_err(|_| StackError::NotFound(format!("Cannot resolve {}", reference)))
+    }
+
+    pub fn merge_base(&self, a: &str, b: &str) -> Result<String, StackError> {
+        command_output("git", &["merge-base", a, b])
+    }
+
+    pub fn checkout_detached(&self, commit: &str) -> Result<(), StackError> {
+        run_command("git", &["checkout", "--detach", commit])
+    }
+
+    pub fn rename_branch(&self, branch_name: &str, new_name: &str) -> Result<(), StackError> {
+        run_command("git", &["branch", "-m", branch_name, new_name])
+    }
+
+    pub fn delete_branch(&self, branch_name: &str) -> Result<(), StackError> {
+        run_command("git", &["branch", "-D", branch_name])
+    }
+
+    pub fn merge_fast_forward(&self, branch_name: &str) -> Result<(), StackError> {
+        run_command("git", &["merge", "--ff-only", branch_name])
+    }
+
+    pub fn merge_squash(&self, branch_name: &str) -> Result<(), StackError> {
+        run_command("git", &["merge", "--squash", branch_name])?;
+        // --no-edit keeps the SQUASH_MSG git prepared, listing every folded commit
+        run_command("git", &["commit", "--no-edit"])
+    }
+
+    /// Commits reachable from `branch` but not `base`, newest first.
+    pub fn log(&self, base: &str, branch: &str) -> Result<Vec<CommitInfo>, StackError> {
+        let range = format!("{}..{}", base, branch);
+        let log = command_output("git", &["log", "--format=%h%x1f%s%x1f%an%x1f%ar", &range])?;
+        Ok(log.lines()
+            .filter_map(|line| {
+                let mut fields = line.split('\x1f');
+                Some(CommitInfo {
+                    sha: fields.next()?.to_string(),
+                    subject: fields.next()?.to_string(),
+                    author: fields.next()?.to_string(),
+                    age: fields.next()?.to_string(),
+                })
+            })
+            .collect())
+    }
+
+    /// Shows the changes on `branch` since it forked from `base`, through git's pager.
+    pub fn show_diff(&self, base: &str, branch: &str, stat: bool) -> Result<(), StackError> {
+        let range = format!("{}...{}", base, branch);
+        let mut args = vec!["diff"];
+        if stat {
+            args.push("--stat");
+        }
+        args.push(&range);
+        let status = Command::new("git")
+            .args(&args)
+            .status()
+            .map_err(|e| StackError::Git(format!("Failed to execute git command: {}", e)))?;
+        if !status.success() {
+            return Err(StackError::Git(format!("git diff {} failed", range)));
+        }
+        Ok(())
+    }
+
+    pub fn diff_stat(&self, base: &str, branch: &str) -> Result<DiffStat, StackError> {
+        let range = format!("{}...{}", base, branch);
+        let numstat = command_output("git", &["diff", "--numstat", &range])?;
+        let mut stat = DiffStat { files: 0, insertions: 0, deletions: 0 };
+        for line in numstat.lines() {
+            let mut fields = line.split('\t');
+            // Binary files report "-" for both counts
+            stat.insertions += fields.next().and_then(|n| n.parse::<usize>().ok()).unwrap_or(0);
+            stat.deletions += fields.next().and_then(|n| n.parse::<usize>().ok()).unwrap_or(0);
+            stat.files += 1;
+        }
+        Ok(stat)
+    }
+
+    pub fn is_ancestor(&self, ancestor: &str, descendant: &str) -> Result<bool, StackError> {
+        let status = Command::new("git")
+            .args(["merge-base", "--is-ancestor", ancestor, descendant])
+            .status()
+            .map_err(|e| StackError::Git(format!("Failed to check ancestry: {}", e)))?;
+        Ok(status.success())
+    }
+
+    pub fn rev_list(&self, range: &str) -> Result<Vec<String>, StackError> {
+        let list = command_output("git", &["rev-list", range])?;
+        Ok(list.lines().map(|line| line.to_string()).collect())
+    }
+
+    pub fn staged_patch(&self) -> Result<String, StackError> {
+        command_output("git", &["diff", "--cached", "--unified=0", "--no-color", "--no-ext-diff", "--no-renames"])
+    }
+
+    /// The changes `commit` made to `path`, without context lines.
+    pub fn commit_patch(&self, commit: &str, path: &str) -> Result<String, StackError> {
+        let parent = format!("{}^", commit);
+        command_output("git", &["diff", "--unified=0", "--no-color", "--no-ext-diff", "--no-renames", &parent, commit, "--", path])
+    }
+
+    /// Full shas of the commits that last touched lines `start..=end` of `path` at `revision`.
+    pub fn blame(&self, revision: &str, path: &str, start: usize, end: usize) -> Result<Vec<String>, StackError> {
+        let range = format!("{},{}", start, end);
+        let blame = command_output("git", &["blame", "--porcelain", "-L", &range, revision, "--", path])?;
+        Ok(blame.lines()
+            .filter_map(|line| {
+                let sha = line.split(' ').next()?;
+                let is_header = sha.len() == 40 && sha.chars().all(|c| c.is_ascii_hexdigit());
+                is_header.then(|| sha.to_string())
+            })
+            .collect())
+    }
+
+    /// Commits `patch` on top of HEAD as a `fixup!` of `target`, through a
+    /// temporary index so the real index and working tree are left alone.
+    pub fn commit_fixup(&self, patch: &str, target: &str) -> Result<(), StackError> {
+        let index = command_output("git", &["rev-parse", "--git-path", "stack-absorb-index"])?;
+        let envs = [("GIT_INDEX_FILE", index.as_str())];
+        let fixup = format!("--fixup={}", target);
+        let result = command_output_with("git", &["read-tree", "HEAD"], &envs, None)
+            .and_then(|_| command_output_with("git", &["apply", "--cached", "--unidiff-zero", "-"], &envs, Some(patch)))
+            .and_then(|_| command_output_with("git", &["commit", "--quiet", "--no-verify", &fixup], &envs, None));
+        let _ = fs::remove_file(&index);
+        result.map(|_| ())
+    }
+
+    /// Squashes `fixup!` commits after `base` into their targets, moving any
+    /// branches in between along with them. Uncommitted changes are stashed
+    /// and restored with their staged state, unlike `rebase --autostash`.
+    pub fn rebase_autosquash(&self, base: &str, options: &RebaseOptions) -> Result<(), StackError> {
+        let mut args = vec!["rebase".to_string(), "--interactive".to_string(), "--autosquash".to_string(),
+            "--update-refs".to_string()];
+        args.extend(options.to_args());
+        args.push(base.to_string());
+        let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+        let stashed = self.is_dirty()?;
+        if stashed {
+            self.stash()?;
+        }
+        // Accept the generated todo list as is
+        let result = command_output_with("git", &args, &[("GIT_SEQUENCE_EDITOR", "true")], None);
+        if result.is_err() {
+            let _ = run_command("git", &["rebase", "--abort"]);
+        }
+        if stashed {
+            run_command("git", &["stash", "pop", "--index"])?;
+        }
+        result.map(|_| ())
+    }
+
+    /// Moves the checked-out branch to `commit`, keeping the index and working tree.
+    pub fn reset_soft(&self, commit: &str) -> Result<(), StackError> {
+        run_command("git", &["reset", "--soft", commit])
+    }
+
+    pub fn set_branch(&self, branch_name: &str, commit: &str) -> Result<(), StackError> {
+        run_command("git", &["branch", "--force", branch_name, commit])
+    }
+
+    /// Opens `contents` in the user's git editor and returns the saved text.
+    pub fn edit(&self, file_name: &str, contents: &str) -> Result<String, StackError> {
+        let path = PathBuf::from(command_output("git", &["rev-parse", "--git-path", file_name])?);
+        fs::write(&path, contents)?;
+
+        let editor = command_output("git", &["var", "GIT_EDITOR"])?;
+        let status = Command::new("sh")
+            .args(["-c", &format!("{} \"$@\"", editor), "editor"])
+            .arg(&path)
+            .status()
+            .map_err(|e| StackError::Git(format!("Failed to launch editor: {}", e)))?;
+        if !status.success() {
+            return Err(StackError::Invalid(format!("Editor {} exited with an error", editor)));
+        }
+
+        let edited = fs::read_to_string(&path)?;
+        fs::remove_file(&path)?;
+        Ok(edited)
+    }
+
+    pub fn amend(&self) -> Result<(), StackError> {
+        run_command("git", &["commit", "--amend", "--no-edit"])
+    }
+
+    pub fn is_dirty(&self) -> Result<bool, StackError> {
+        let status = command_output("git", &["status", "--porcelain", "--untracked-files=no"])?;
+        Ok(!status.trim().is_empty())
+    }
+
+    pub fn stash(&self) -> Result<(), StackError> {
+        run_command("git", &["stash", "push", "--message", "stack autostash"])
+    }
+
+    pub fn stash_pop(&self) -> Result<(), StackError> {
+        run_command("git", &["stash", "pop"])
+    }
+
+    pub fn remote_url(&self, remote: &str) -> Result<String, StackError> {
+        command_output("git", &["remote", "get-url", remote])
+    }
+
+    /// The password git's credential helpers store for `host`, if any. Never
+    /// prompts: a missing credential is reported as `None`.
+    pub fn credential_fill(&self, host: &str) -> Result<Option<String>, StackError> {
+        self.credential_fill_with(host, &[])
+    }
+
+    /// `credential_fill` with `config` entries ("key=value") passed to git as
+    /// `-c` overrides, e.g. to choose the credential helper.
+    pub fn credential_fill_with(&self, host: &str, config: &[&str]) -> Result<Option<String>, StackError> {
+        let input = format!("protocol=https\nhost={}\n\n", host);
+        let mut args = Vec::new();
+        for entry in config {
+            args.extend(["-c", entry]);
+        }
+        args.extend(["credential", "fill"]);
+        let output = command_output_with(
+            "git",
+            &args,
+            &[("GIT_TERMINAL_PROMPT", "0"), ("GIT_ASKPASS", "true"), ("SSH_ASKPASS", "true")],
+            Some(&input),
+        );
+        let Ok(output) = output else {
+            return Ok(None);
+        };
+        Ok(output.lines()
+            .find_map(|line| line.strip_prefix("password="))
+            .filter(|password| !password.is_empty())
+            .map(|password| password.to_string()))
+    }
+
+    /// Updates the remote-tracking refs of `branches` from `remote`.
+    pub fn fetch_branches(&self, remote: &str, branches: &[String]) -> Result<(), StackError> {
+        let refspecs: Vec<String> = branches.iter()
+            .map(|branch| format!("+refs/heads/{}:refs/remotes/{}/{}", branch, remote, branch))
+            .collect();
+        let mut args = vec!["fetch", remote];
+        args.extend(refspecs.iter().map(String::as_str));
+        run_command("git", &args)
+    }
+
+    pub fn create_tracking_branch(&self, branch_name: &str, upstream: &str) -> Result<(), StackError> {
+        run_command("git", &["branch", "--track", branch_name, upstream])
+    }
+
+    pub fn push_branch(&self, remote: &str, branch_name: &str) -> Result<(), StackError> {
+        run_command("git", &["push", "--force-with-lease", "--set-upstream", remote, branch_name])
+    }
+
     pub fn pull(&self) -> Result<(), StackError> {
         run_command("git", &["pull"])
     }
@@ -93,17 +493,50 @@ impl Git {
         run_command("git", &["checkout", branch_name])
     }
 
-    pub fn rebase(&self, target_branch: &str) -> Result<(), StackError> {
+    pub fn rebase(&self, target_branch: &str, options: &RebaseOptions) -> Result<(), StackError> {
         if !self.check_branch_exists(target_branch)? {
             let err = StackError::Invalid(format!("Target branch {} does not exist", target_branch));
             error(&err);
             return Err(err);
         }
 
-        run_command("git", &["rebase", "--committer-date-is-author-date", target_branch])
+        let mut args = vec!["rebase".to_string()];
+        args.extend(options.to_args());
+        args.push(target_branch.to_string());
+        let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+        run_command("git", &args)
+    }
+
+    /// Replays the commits of `target_branch` after `upstream` onto `base_branch`.
+    pub fn rebase_onto_upstream(&self, target_branch: &str, base_branch: &str, upstream: &str, options: &RebaseOptions) -> Result<(), StackError> {
+        if !self.check_branch_exists(target_branch)? {
+            let err = StackError::Invalid(format!("Target branch {} does not exist", target_branch));
+            error(&err);
+            return Err(err);
+        }
+
+        let mut args = vec!["rebase".to_string()];
+        args.extend(options.to_args());
+        args.extend(["--onto".to_string(), base_branch.to_string(), upstream.to_string(), target_branch.to_string()]);
+        let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+        run_command("git", &args)
+    }
+
+    /// Replays `upstream..commit` onto `base` on a detached HEAD, leaving every
+    /// branch untouched. Aborts the rebase if it fails.
+    pub fn rebase_detached(&self, commit: &str, base: &str, upstream: &str, options: &RebaseOptions) -> Result<String, StackError> {
+        let mut args = vec!["rebase".to_string()];
+        args.extend(options.to_args());
+        args.extend(["--onto".to_string(), base.to_string(), upstream.to_string(), commit.to_string()]);
+        let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+        if let Err(e) = run_command("git", &args) {
+            let _ = run_command("git", &["rebase", "--abort"]);
+            return Err(e);
+        }
+        self.rev_parse("HEAD")
     }
 
-    pub fn rebase_onto(&self, target_branch: &str, base_branch: &str) -> Result<(), StackError> {
+    pub fn rebase_onto(&self, target_branch: &str, base_branch: &str, options: &RebaseOptions) -> Result<(), StackError> {
         if !self.check_branch_exists(target_branch)? {
             let err = StackError::Invalid(format!("Target branch {} does not exist", target_branch));
             error(&err);
@@ -116,6 +549,6 @@ impl Git {
         }
 
         self.checkout(target_branch)?;
-        self.rebase(base_branch)
+        self.rebase(base_branch, options)
     }
 }
\ No newline at end of file
@@ -1 +1,2 @@
-pub mod git;
\ No newline at end of file
+pub mod git;
+pub mod diff;
\ No newline at end of file
@@ -0,0 +1,85 @@
+use std::fs;
+use std::path::Path;
+use crate::error::StackError;
+
+const HOOK_MARKER: &str = "# installed by stack";
+const CHAINED_SUFFIX: &str = ".pre-stack";
+
+pub const HOOKS: [&str; 2] = ["post-checkout", "post-rewrite"];
+
+fn hook_script(hook: &str) -> String {
+    // post-rewrite receives the rewritten commits on stdin, which has to be
+    // replayed to both the chained hook and stack.
+    if hook == "post-rewrite" {
+        format!(
+"#!/bin/sh
+{marker}
+input=$(cat)
+if [ -x \"$0{suffix}\" ]; then
+    printf '%s\\n' \"$input\" | \"$0{suffix}\" \"$@\" || exit $?
+fi
+printf '%s\\n' \"$input\" | stack hooks {hook} \"$@\"
+exit 0
+",
+            marker = HOOK_MARKER,
+            suffix = CHAINED_SUFFIX,
+            hook = hook,
+        )
+    } else {
+        format!(
+"#!/bin/sh
+{marker}
+if [ -x \"$0{suffix}\" ]; then
+    \"$0{suffix}\" \"$@\" || exit $?
+fi
+stack hooks {hook} \"$@\"
+exit 0
+",
+            marker = HOOK_MARKER,
+            suffix = CHAINED_SUFFIX,
+            hook = hook,
+        )
+    }
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<(), StackError> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_mode(0o755);
+    fs::set_permissions(path, permissions)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<(), StackError> {
+    Ok(())
+}
+
+/// Installs `hook` into `hooks_dir`. An existing hook that was not installed by
+/// stack is moved aside and chained. Returns true if a hook was chained.
+pub fn install_hook(hooks_dir: &Path, hook: &str) -> Result<bool, StackError> {
+    fs::create_dir_all(hooks_dir)?;
+    let hook_path = hooks_dir.join(hook);
+    let mut chained = false;
+
+    if hook_path.exists() {
+        let existing = fs::read_to_string(&hook_path).unwrap_or_default();
+        if !existing.contains(HOOK_MARKER) {
+            let chained_path = hooks_dir.join(format!("{}{}", hook, CHAINED_SUFFIX));
+            if chained_path.exists() {
+                return Err(StackError::Invalid(format!(
+                    "Cannot chain existing {} hook: {} already exists",
+                    hook,
+                    chained_path.display()
+                )));
+            }
+            fs::rename(&hook_path, &chained_path)?;
+            chained = true;
+        }
+    }
+
+    fs::write(&hook_path, hook_script(hook))?;
+    make_executable(&hook_path)?;
+    Ok(chained)
+}
@@ -0,0 +1 @@
+pub mod hooks;
\ No newline at end of file
@@ -7,11 +7,13 @@ mod error;
 mod output;
 mod git;
 mod config;
+mod hooks;
+mod forge;
 
 fn main() {
     let cli = Cli::parse();
     
-    if let Err(_) = cli::cmd::execute(cli.command) {
+    if let Err(_) = cli::cmd::execute(cli.command, &cli.config) {
         std::process::exit(1);
     }
 }
@@ -1,15 +1,22 @@
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::fs::OpenOptions;
 use std::io::Write;
 use crate::error::StackError;
 use crate::output::{error, success, info, question_string, question_bool, warning};
 use std::fs;
-use crate::config::config::Config;
+use crate::config::config::{Config, ConfigOrigin};
+use crate::config::file::{parse_config_file, render_config_file};
 
 const STACK_DIR: &str = ".stack";
 const CURRENT_STACK_PATH: &str = "current";
 const STACKS_DIR: &str = "stacks";
 const CONFIG_FILE: &str = "config";
+const LABELS_DIR: &str = "labels";
+const SETTINGS_DIR: &str = "settings";
+const PULL_REQUESTS_FILE: &str = "prs";
+const CACHE_DIR: &str = "cache";
+const STATUS_CACHE_FILE: &str = "status";
 
 
 fn find_repository_root(start_dir: &Path) -> Result<PathBuf, StackError> {
@@ -30,42 +37,74 @@ fn find_repository_root(start_dir: &Path) -> Result<PathBuf, StackError> {
     }
 }
 
-fn user_configuration() -> Result<Config, StackError> {
-    let main_branch = question_string("What is your main branch name? (default: main)", "main")?;
-    let confirmation_on_git_push = question_bool("Require confirmation on push?", true)?;
-    let confirmation_on_git_rebase = question_bool("Require confirmation on rebase?", true)?;
+/// `$XDG_CONFIG_HOME/stack/config`, else `~/.config/stack/config`.
+pub fn user_config_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("stack").join(CONFIG_FILE))
+}
 
-    Ok(Config {
-        MAIN_BRANCH_NAME: main_branch,
-        CONFIRMATION_ON_GIT_PUSH: confirmation_on_git_push,
-        CONFIRMATION_ON_GIT_REBASE: confirmation_on_git_rebase,
-    })
+// Defaults overridden by the user config, if there is one
+pub fn user_config() -> Result<Config, StackError> {
+    let mut config = Config::new();
+    if let Some(path) = user_config_path()
+        && path.exists()
+    {
+        config.apply_file(&fs::read_to_string(&path)?, &ConfigOrigin::File(path))?;
+    }
+    Ok(config)
 }
 
-pub fn init(path_dir: &Path) {
-    match find_repository_root(path_dir) {
-        Ok(dir) => {
-            success(&format!("Stack directory already exists at: {}", dir.display()));
-        }
-        Err(_) => {
-            let stack_dir = path_dir.join(STACK_DIR);
-            if !stack_dir.exists() {
-                let stacks_dir = stack_dir.join(STACKS_DIR);
-                let config_file = stack_dir.join(CONFIG_FILE);
-                fs::create_dir_all(&stacks_dir).unwrap();
-                
-                let config = user_configuration().unwrap();
-                fs::write(&config_file, config.to_string()).unwrap();
-                success("Stack directory created successfully!");
-            } else {
-                error(&StackError::Invalid("Stack directory already exists!".to_string()));
-            }
+// Asks for the repository's settings. Preferences already in the user config
+// are not asked again, so they keep applying to this repository. With
+// `assume_yes` nothing is asked and the defaults are written.
+fn user_configuration(main_branch: Option<String>, detected_main: Option<String>, assume_yes: bool) -> Result<String, StackError> {
+    let defaults = user_config()?;
+    let default_main = detected_main.unwrap_or(defaults.MAIN_BRANCH_NAME.clone());
+    let main_branch = match main_branch {
+        Some(main_branch) => main_branch,
+        None if assume_yes => default_main,
+        None => question_string(
+            &format!("What is your main branch name? (default: {})", default_main),
+            &default_main
+        )?,
+    };
+    let mut settings = vec![("MAIN_BRANCH_NAME".to_string(), main_branch)];
+    for (key, question) in [
+        ("CONFIRMATION_ON_GIT_PUSH", "Require confirmation on push?"),
+        ("CONFIRMATION_ON_GIT_REBASE", "Require confirmation on rebase?"),
+    ] {
+        if defaults.origins.contains_key(key) {
+            continue;
         }
+        let confirmation = assume_yes || question_bool(question, true)?;
+        settings.push((key.to_string(), confirmation.to_string()));
     }
+    Ok(render_config_file(&settings))
+}
+
+/// Creates `.stack` at `root`, the top of the git work tree.
+pub fn init(root: &Path, main_branch: Option<String>, detected_main: Option<String>, assume_yes: bool) -> Result<(), StackError> {
+    let stack_dir = root.join(STACK_DIR);
+    if stack_dir.exists() {
+        success(&format!("Stack directory already exists at: {}", root.display()));
+        return Ok(());
+    }
+    let config = user_configuration(main_branch, detected_main, assume_yes)?;
+    fs::create_dir_all(stack_dir.join(STACKS_DIR))?;
+    fs::write(stack_dir.join(CONFIG_FILE), config)?;
+    success(&format!("Stack directory created at: {}", root.display()));
+    Ok(())
 }
 
 pub struct FsStore {
     stacks_dir: PathBuf,
+    labels_dir: PathBuf,
+    settings_dir: PathBuf,
+    pull_requests_file: PathBuf,
+    status_cache_file: PathBuf,
     current_stack: PathBuf,
     config_file: PathBuf,
 }
@@ -78,13 +117,28 @@ impl FsStore {
             e
         })?;
         info(&format!("Stack directory found at: {:?}", root_dir));
+        Self::open(&root_dir)
+    }
+
+    // Like `new`, but silent, for callers such as git hooks that run on every
+    // checkout and must not print anything when stack is not in use.
+    pub fn find(start_dir: &Path) -> Result<Self, StackError> {
+        let root_dir = find_repository_root(start_dir)?;
+        Self::open(&root_dir)
+    }
+
+    fn open(root_dir: &Path) -> Result<Self, StackError> {
         let stack_dir = root_dir.join(STACK_DIR);
         let stacks_dir = stack_dir.join(STACKS_DIR);
+        let labels_dir = stack_dir.join(LABELS_DIR);
+        let settings_dir = stack_dir.join(SETTINGS_DIR);
+        let pull_requests_file = stack_dir.join(PULL_REQUESTS_FILE);
+        let status_cache_file = stack_dir.join(CACHE_DIR).join(STATUS_CACHE_FILE);
         let current_stack = stack_dir.join(CURRENT_STACK_PATH);
         let config_file = stack_dir.join(CONFIG_FILE);
 
         fs::create_dir_all(&stacks_dir)?;
-        Ok(Self { stacks_dir, current_stack, config_file })
+        Ok(Self { stacks_dir, labels_dir, settings_dir, pull_requests_file, status_cache_file, current_stack, config_file })
     }
 
     fn get_stack_path(&self, stack_name: &str) -> PathBuf {
@@ -138,6 +192,61 @@ impl FsStore {
             return Err(StackError::Invalid(format!("Stack {} does not exist.", stack_name)));
         }
         fs::remove_file(&stack_dir)?;
+        for path in [self.labels_dir.join(stack_name), self.settings_dir.join(stack_name)] {
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_labels(&self, stack_name: &str) -> Result<HashMap<String, String>, StackError> {
+        let labels_path = self.labels_dir.join(stack_name);
+        if !labels_path.exists() {
+            return Ok(HashMap::new());
+        }
+        let contents = fs::read_to_string(&labels_path)?;
+        Ok(contents.lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(branch, label)| (branch.to_string(), label.to_string()))
+            .collect())
+    }
+
+    pub fn set_labels(&self, stack_name: &str, labels: &HashMap<String, String>) -> Result<(), StackError> {
+        fs::create_dir_all(&self.labels_dir)?;
+        let mut lines: Vec<String> = labels.iter()
+            .map(|(branch, label)| format!("{}={}", branch, label))
+            .collect();
+        lines.sort();
+        fs::write(self.labels_dir.join(stack_name), lines.join("\n"))?;
+        Ok(())
+    }
+
+    // Settings overriding the repository config for one stack
+    pub fn get_stack_settings(&self, stack_name: &str) -> Result<HashMap<String, String>, StackError> {
+        let settings_path = self.settings_dir.join(stack_name);
+        if !settings_path.exists() {
+            return Ok(HashMap::new());
+        }
+        let contents = fs::read_to_string(&settings_path)?;
+        Ok(contents.lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect())
+    }
+
+    pub fn set_stack_setting(&self, stack_name: &str, key: &str, value: Option<&str>) -> Result<(), StackError> {
+        let mut settings = self.get_stack_settings(stack_name)?;
+        match value {
+            Some(value) => settings.insert(key.to_string(), value.to_string()),
+            None => settings.remove(key),
+        };
+        fs::create_dir_all(&self.settings_dir)?;
+        let mut lines: Vec<String> = settings.iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        lines.sort();
+        fs::write(self.settings_dir.join(stack_name), lines.join("\n"))?;
         Ok(())
     }
 
@@ -238,29 +347,140 @@ impl FsStore {
         Ok(())
     }
 
+    pub fn set_stack_contents(&self, branches: &[String]) -> Result<(), StackError> {
+        let current_stack = self.get_current_stack_path()?;
+        let stack_dir = self.get_stack_path(&current_stack);
+        self.write_to_stack(&stack_dir, branches)
+    }
+
+    pub fn replace_in_stack(&self, index: usize, branches: &[String]) -> Result<(), StackError> {
+        let current_stack = self.get_current_stack_path()?;
+        let stack_dir = self.get_stack_path(&current_stack);
+
+        let mut contents = self.get_stack_contents(&current_stack)?;
+        if index >= contents.len() {
+            return Err(StackError::Invalid(format!("Index {} is out of bounds", index)));
+        }
+
+        contents.splice(index..=index, branches.iter().cloned());
+        self.write_to_stack(&stack_dir, &contents)?;
+        Ok(())
+    }
+
     pub fn get_stacks(&self) -> Result<Vec<String>, StackError> {
         Ok(fs::read_dir(&self.stacks_dir)?
             .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
             .collect())
     }
 
+    pub fn find_stacks_with_branch(&self, branch_name: &str) -> Result<Vec<(String, usize)>, StackError> {
+        let mut stacks = self.get_stacks()?;
+        stacks.sort();
+        let mut matches = Vec::new();
+        for stack in stacks {
+            let contents = self.get_stack_contents(&stack)?;
+            if let Some(index) = contents.iter().position(|b| b == branch_name) {
+                matches.push((stack, index));
+            }
+        }
+        Ok(matches)
+    }
+
+    pub fn get_pull_requests(&self) -> Result<HashMap<String, u64>, StackError> {
+        if !self.pull_requests_file.exists() {
+            return Ok(HashMap::new());
+        }
+        let contents = fs::read_to_string(&self.pull_requests_file)?;
+        Ok(contents.lines()
+            .filter_map(|line| line.split_once('='))
+            .filter_map(|(branch, number)| Some((branch.to_string(), number.parse().ok()?)))
+            .collect())
+    }
+
+    pub fn set_pull_request(&self, branch_name: &str, number: Option<u64>) -> Result<(), StackError> {
+        let mut pull_requests = self.get_pull_requests()?;
+        match number {
+            Some(number) => pull_requests.insert(branch_name.to_string(), number),
+            None => pull_requests.remove(branch_name),
+        };
+        let mut lines: Vec<String> = pull_requests.iter()
+            .map(|(branch, number)| format!("{}={}", branch, number))
+            .collect();
+        lines.sort();
+        fs::write(&self.pull_requests_file, lines.join("\n"))?;
+        Ok(())
+    }
+
+    // Cached pull request statuses, keyed by number: (unix time fetched, status)
+    pub fn get_status_cache(&self) -> Result<HashMap<u64, (u64, String)>, StackError> {
+        if !self.status_cache_file.exists() {
+            return Ok(HashMap::new());
+        }
+        let contents = fs::read_to_string(&self.status_cache_file)?;
+        Ok(contents.lines()
+            .filter_map(|line| {
+                let (number, rest) = line.split_once('=')?;
+                let (fetched_at, status) = rest.split_once(' ')?;
+                Some((number.parse().ok()?, (fetched_at.parse().ok()?, status.to_string())))
+            })
+            .collect())
+    }
+
+    pub fn set_status_cache(&self, cache: &HashMap<u64, (u64, String)>) -> Result<(), StackError> {
+        if let Some(dir) = self.status_cache_file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut lines: Vec<String> = cache.iter()
+            .map(|(number, (fetched_at, status))| format!("{}={} {}", number, fetched_at, status))
+            .collect();
+        lines.sort();
+        fs::write(&self.status_cache_file, lines.join("\n"))?;
+        Ok(())
+    }
+
+    /// The user config with the repository config applied on top.
     pub fn read_config_file(&self) -> Result<Config, StackError> {
         if !self.config_file.exists() {
             warning("No config file found.");
-            let config = user_configuration()?;
-            fs::write(&self.config_file, config.to_string())?;
-            return Ok(config);
+            fs::write(&self.config_file, user_configuration(None, None, false)?)?;
         }
 
+        let mut config = user_config()?;
         let contents = fs::read_to_string(&self.config_file)?;
-        let config = Config::from_string(contents)?;
+        config.apply_file(&contents, &ConfigOrigin::File(self.config_file.clone()))?;
         Ok(config)
     }
 
+    fn read_config_settings(&self) -> Result<Vec<(String, String)>, StackError> {
+        if !self.config_file.exists() {
+            return Ok(Vec::new());
+        }
+        parse_config_file(&fs::read_to_string(&self.config_file)?)
+    }
+
+    // Only the repository's own settings are written, so keys it does not
+    // set keep coming from the user config. Older KEY=VALUE files are
+    // rewritten as TOML.
     pub fn update_config(&self, key: &str, value: &str) -> Result<(), StackError> {
-        let mut config = self.read_config_file()?;
-        config.set_kv(key, value);
-        fs::write(&self.config_file, config.to_string())?;
+        let mut settings = self.read_config_settings()?;
+        match settings.iter_mut().find(|(existing, _)| existing == key) {
+            Some(setting) => setting.1 = value.to_string(),
+            None => settings.push((key.to_string(), value.to_string())),
+        }
+        fs::write(&self.config_file, render_config_file(&settings))?;
         Ok(())
     }
+
+    /// Removes a setting from the repository config. Returns false if it
+    /// was not set there.
+    pub fn unset_config(&self, key: &str) -> Result<bool, StackError> {
+        let mut settings = self.read_config_settings()?;
+        let count = settings.len();
+        settings.retain(|(existing, _)| existing != key);
+        if settings.len() == count {
+            return Ok(false);
+        }
+        fs::write(&self.config_file, render_config_file(&settings))?;
+        Ok(true)
+    }
 }